@@ -0,0 +1,248 @@
+use crate::game::buildings::Building;
+use crate::game::game_state::GameState;
+use crate::game::map_layout::MapLayout;
+use crate::game::tile::{Landscape, Tile};
+
+/// Characters between two columns of tiles.
+const COLUMN_WIDTH: usize = 16;
+const BOX_WIDTH: usize = 13;
+/// Lines per half row, a tile is three lines high plus one for the road below it.
+const ROW_HEIGHT: usize = 2;
+
+const RESET: &str = "\x1b[0m";
+const DIM: &str = "\x1b[2m";
+const ROAD: &str = "\x1b[1;33m";
+
+/// Draws the board as text. Every tile is a box with its index, landscape and the three slots:
+///
+/// ```text
+/// +-[ 6]------+
+/// |" Plains   |
+/// |Sch Zoo  . |
+/// ```
+///
+/// Tiles that are not explored yet have a dotted border. Built roads are drawn with `|`, `-`, `/` and `\`,
+/// roads that could still be built with `.`. Tiles are placed where the map's layout puts them.
+/// With `colour` the landscapes and roads are coloured with ANSI escape codes.
+pub fn render(state: &GameState, colour: bool) -> String {
+    let layout = state.layout();
+    let mut canvas = Canvas::new();
+    for (index, tile) in state.tiles.iter().enumerate() {
+        draw_tile(&mut canvas, layout, index, tile);
+    }
+    for from in 0..layout.len() {
+        for &to in layout.neighbours(from) {
+            let built = state.tiles[from].connections.contains(to) || state.tiles[to].connections.contains(from);
+            draw_road(&mut canvas, layout, from, to, built);
+        }
+    }
+    canvas.to_string(colour)
+}
+
+pub fn landscape_glyph(landscape: Landscape) -> char {
+    match landscape {
+        Landscape::Plains => '"',
+        Landscape::Ocean => '~',
+        Landscape::Mountain => '^',
+        Landscape::Swamp => '%',
+        Landscape::Desert => ':',
+        Landscape::Forest => '*',
+    }
+}
+
+/// Three letter names, multi-space buildings show up in every space they fill.
+pub fn building_abbreviation(building: Building) -> &'static str {
+    match building {
+        Building::Factory => "Fac",
+        Building::Store => "Sto",
+        Building::CoalPowerPlant => "Coa",
+        Building::Trees => "Tre",
+        Building::River => "Riv",
+        Building::Livestock => "Liv",
+        Building::Field => "Fld",
+        Building::SolarPark => "Sol",
+        Building::OffshoreTurbines => "Off",
+        Building::Biotope => "Bio",
+        Building::NationalPark => "NaP",
+        Building::EnvironmentalProtectionArea => "EPA",
+        Building::School => "Sch",
+        Building::Museum => "Mus",
+        Building::Zoo => "Zoo",
+        Building::Library => "Lib",
+        Building::University => "Uni",
+        Building::Empty => " . ",
+    }
+}
+
+fn landscape_colour(landscape: Landscape) -> &'static str {
+    match landscape {
+        Landscape::Plains => "\x1b[92m",
+        Landscape::Ocean => "\x1b[94m",
+        Landscape::Mountain => "\x1b[37m",
+        Landscape::Swamp => "\x1b[36m",
+        Landscape::Desert => "\x1b[93m",
+        Landscape::Forest => "\x1b[32m",
+    }
+}
+
+fn draw_tile(canvas: &mut Canvas, layout: &MapLayout, index: usize, tile: &Tile) {
+    let (x, y) = origin(layout, index);
+    let colour = if tile.usable { landscape_colour(tile.landscape) } else { DIM };
+    let (corner, top, side) = if tile.usable { ('+', '-', '|') } else { ('.', '.', ':') };
+
+    let label = format!("[{index:>2}]");
+    let border = format!("{corner}{top}{label}{}{corner}", top.to_string().repeat(BOX_WIDTH - 3 - label.len()));
+    let name = format!("{:?}", tile.landscape);
+    let landscape = format!("{side}{} {name:<w$}{side}", landscape_glyph(tile.landscape), w = BOX_WIDTH - 4);
+    let slots: Vec<&str> = tile.spaces.iter().map(|&b| building_abbreviation(b)).collect();
+    let slots = format!("{side}{}{side}", slots.join(" "));
+    for (line, text) in [border, landscape, slots].iter().enumerate() {
+        canvas.write(x, y + line, text, colour);
+    }
+}
+
+/// Vertical neighbours get a road in the line between them, horizontal ones in the gap
+/// between the boxes and diagonal ones a three character slope between the middle lines.
+fn draw_road(canvas: &mut Canvas, layout: &MapLayout, from: usize, to: usize, built: bool) {
+    let positions = layout.positions();
+    let key = |tile: usize| (positions[tile].1, positions[tile].0);
+    let (upper, lower) = if key(from) < key(to) { (from, to) } else { (to, from) };
+    let ((upper_column, upper_row), (lower_column, lower_row)) = (positions[upper], positions[lower]);
+    let (x, y) = origin(layout, upper);
+    match (lower_column - upper_column, lower_row - upper_row) {
+        (0, 2) => canvas.put(x + BOX_WIDTH / 2, y + 3, if built { '|' } else { '.' }, ROAD),
+        (1, 0) => canvas.write(x + BOX_WIDTH, y + 1, if built { "---" } else { " . " }, ROAD),
+        (1, 1) => {
+            for step in 0..3 {
+                canvas.put(x + BOX_WIDTH + step, y + 1 + step, if built { '\\' } else { '.' }, ROAD);
+            }
+        }
+        (-1, 1) => {
+            for step in 0..3 {
+                canvas.put(x - 1 - step, y + 1 + step, if built { '/' } else { '.' }, ROAD);
+            }
+        }
+        // Not neighbours on the grid, there is nothing sensible to draw.
+        _ => {}
+    }
+}
+
+/// Top left corner of a tile's box.
+fn origin(layout: &MapLayout, index: usize) -> (usize, usize) {
+    let positions = layout.positions();
+    let min_column = positions.iter().map(|p| p.0).min().unwrap_or(0);
+    let min_row = positions.iter().map(|p| p.1).min().unwrap_or(0);
+    let (column, row) = positions[index];
+    (
+        (column - min_column) as usize * COLUMN_WIDTH,
+        (row - min_row) as usize * ROW_HEIGHT,
+    )
+}
+
+/// A grid of characters, each with the escape code it should be printed with.
+struct Canvas {
+    cells: Vec<Vec<(char, &'static str)>>,
+}
+
+impl Canvas {
+    fn new() -> Canvas {
+        Canvas { cells: Vec::new() }
+    }
+
+    fn put(&mut self, x: usize, y: usize, character: char, colour: &'static str) {
+        if self.cells.len() <= y {
+            self.cells.resize(y + 1, Vec::new());
+        }
+        let line = &mut self.cells[y];
+        if line.len() <= x {
+            line.resize(x + 1, (' ', RESET));
+        }
+        line[x] = (character, colour);
+    }
+
+    fn write(&mut self, x: usize, y: usize, text: &str, colour: &'static str) {
+        for (offset, character) in text.chars().enumerate() {
+            self.put(x + offset, y, character, colour);
+        }
+    }
+
+    fn to_string(&self, colour: bool) -> String {
+        let mut out = String::new();
+        for line in &self.cells {
+            let mut current = RESET;
+            for &(character, code) in line {
+                if colour && code != current && character != ' ' {
+                    out.push_str(code);
+                    current = code;
+                }
+                out.push(character);
+            }
+            if colour && current != RESET {
+                out.push_str(RESET);
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::buildings::Building::School;
+    use crate::game::game_state::Action::{Build, BuildInfrastructure};
+    use crate::game::ruleset::Ruleset;
+    use std::sync::Arc;
+
+    #[test]
+    fn map_shows_tiles_slots_and_roads() {
+        let mut state = GameState::initialize_with_seed(5);
+        state.advance(Build(School, 6));
+        state.advance(BuildInfrastructure(6, 2));
+        let map = render(&state, false);
+        let lines: Vec<&str> = map.lines().collect();
+
+        let layout = state.layout();
+        let (x, y) = origin(layout, 6);
+        assert_eq!(&lines[y][x..x + BOX_WIDTH], "+-[ 6]------+");
+        assert!(lines[y + 2][x..].starts_with("|Sch  .   . |"));
+        let (x, y) = origin(layout, 2);
+        assert!(lines[y][x..].starts_with("+-[ 2]"));
+        assert_eq!(lines[y + 3].chars().nth(x + BOX_WIDTH / 2), Some('|'));
+        let (x, y) = origin(layout, 12);
+        assert!(lines[y][x..].starts_with("..[12]....."));
+        assert!(!map.contains('\x1b'));
+    }
+
+    #[test]
+    fn rectangles_get_horizontal_roads() {
+        let layout = Arc::new(MapLayout::rectangle(3, 3).unwrap());
+        let state = GameState::initialize_with_layout(5, Ruleset::shared_default(), layout.clone());
+        let map = render(&state, false);
+        let lines: Vec<&str> = map.lines().collect();
+
+        let (x, y) = origin(&layout, 4);
+        assert!(lines[y][x..].starts_with("+-[ 4]"));
+        assert!(lines[y + 1][x + BOX_WIDTH..].starts_with(" . "));
+        let (x, y) = origin(&layout, 8);
+        assert!(lines[y][x..].starts_with("..[ 8]"));
+    }
+
+    #[test]
+    fn colour_only_adds_escape_codes() {
+        let state = GameState::initialize_with_seed(5);
+        let coloured = render(&state, true);
+        assert!(coloured.contains(ROAD));
+
+        let mut stripped = String::new();
+        let mut chars = coloured.chars();
+        while let Some(character) = chars.next() {
+            if character == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                stripped.push(character);
+            }
+        }
+        assert_eq!(stripped, render(&state, false));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::game::buildings::Building;
+use crate::game::game_state::{Action, GameState};
+use std::fmt;
+use std::str::FromStr;
+
+pub const DEFAULT_SAVE_PATH: &str = "saved.json";
+pub const DEFAULT_REPLAY_PATH: &str = "replay.json";
+
+pub const HELP: &str = "\
+Commands:
+  <number>                  play the legal action with that number
+  build <Building> <tile>   e.g. build SolarPark 4
+  connect <from> <to>       build infrastructure between two tiles
+  terraform <tile>          turn a tile into plains
+  hint                      show the AI's best move and plan
+  undo, redo                step through the history (also u, r)
+  save [path]               save the game, default saved.json
+  load [path]               load a saved game
+  replay [path]             export an annotated replay, default replay.json
+  help                      show this list
+  quit                      leave the game (also x)";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Play(Action),
+    Hint,
+    Undo,
+    Redo,
+    Save(String),
+    Load(String),
+    ExportReplay(String),
+    Help,
+    Quit,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    UnexpectedArgument(String),
+    InvalidNumber(String),
+    UnknownBuilding(String),
+    ChoiceOutOfRange { choice: usize, choices: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "no command given, type help for a list"),
+            ParseError::UnknownCommand(command) => write!(f, "unknown command {command}, type help for a list"),
+            ParseError::MissingArgument(argument) => write!(f, "missing {argument}"),
+            ParseError::UnexpectedArgument(argument) => write!(f, "unexpected {argument}"),
+            ParseError::InvalidNumber(number) => write!(f, "{number} is not a number"),
+            ParseError::UnknownBuilding(name) => write!(f, "there is no building called {name}"),
+            ParseError::ChoiceOutOfRange { choice, choices } => {
+                write!(f, "there is no action {choice}, pick one from 0 to {}", choices.saturating_sub(1))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses one line of player input. Numbered choices are resolved against the legal actions,
+/// textual actions are only checked for syntax, whether they are legal is up to the game.
+pub fn parse(line: &str, state: &GameState) -> Result<Command, ParseError> {
+    let mut words = line.split_whitespace();
+    let Some(command) = words.next() else {
+        return Err(ParseError::Empty);
+    };
+    let parsed = match command.to_ascii_lowercase().as_str() {
+        "build" => {
+            let name = words.next().ok_or(ParseError::MissingArgument("building"))?;
+            let building = Building::from_str(name).map_err(|_| ParseError::UnknownBuilding(name.to_string()))?;
+            Command::Play(Action::Build(building, tile(words.next(), "tile")?))
+        }
+        "connect" => {
+            let from = tile(words.next(), "tile to connect from")?;
+            Command::Play(Action::BuildInfrastructure(from, tile(words.next(), "tile to connect to")?))
+        }
+        "terraform" => Command::Play(Action::Terraform(tile(words.next(), "tile")?)),
+        "hint" => Command::Hint,
+        "undo" | "u" => Command::Undo,
+        "redo" | "r" => Command::Redo,
+        "save" => Command::Save(words.next().unwrap_or(DEFAULT_SAVE_PATH).to_string()),
+        "load" => Command::Load(words.next().unwrap_or(DEFAULT_SAVE_PATH).to_string()),
+        "replay" => Command::ExportReplay(words.next().unwrap_or(DEFAULT_REPLAY_PATH).to_string()),
+        "help" | "?" => Command::Help,
+        "quit" | "exit" | "x" => Command::Quit,
+        choice if choice.starts_with(|c: char| c.is_ascii_digit()) => {
+            let choice = number(choice)?;
+            let action = state.legal_actions.get(choice).ok_or(ParseError::ChoiceOutOfRange {
+                choice,
+                choices: state.legal_actions.len(),
+            })?;
+            Command::Play(*action)
+        }
+        _ => return Err(ParseError::UnknownCommand(command.to_string())),
+    };
+    match words.next() {
+        Some(extra) => Err(ParseError::UnexpectedArgument(extra.to_string())),
+        None => Ok(parsed),
+    }
+}
+
+fn tile(word: Option<&str>, argument: &'static str) -> Result<usize, ParseError> {
+    number(word.ok_or(ParseError::MissingArgument(argument))?)
+}
+
+fn number(word: &str) -> Result<usize, ParseError> {
+    word.parse().map_err(|_| ParseError::InvalidNumber(word.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::buildings::Building::SolarPark;
+
+    #[test]
+    fn parses_textual_and_numbered_actions() {
+        let state = GameState::initialize_with_seed(5);
+        assert_eq!(parse("build SolarPark 4", &state), Ok(Command::Play(Action::Build(SolarPark, 4))));
+        assert_eq!(parse("BUILD solarpark 4\n", &state), Ok(Command::Play(Action::Build(SolarPark, 4))));
+        assert_eq!(parse("connect 6 8", &state), Ok(Command::Play(Action::BuildInfrastructure(6, 8))));
+        assert_eq!(parse("terraform 3", &state), Ok(Command::Play(Action::Terraform(3))));
+        assert_eq!(parse(" 0 ", &state), Ok(Command::Play(state.legal_actions[0])));
+        assert_eq!(parse("save", &state), Ok(Command::Save(DEFAULT_SAVE_PATH.to_string())));
+        assert_eq!(parse("load other.json", &state), Ok(Command::Load("other.json".to_string())));
+        assert_eq!(parse("x", &state), Ok(Command::Quit));
+    }
+
+    #[test]
+    fn bad_input_is_an_error_instead_of_a_panic() {
+        let state = GameState::initialize_with_seed(5);
+        let choices = state.legal_actions.len();
+        assert_eq!(parse("", &state), Err(ParseError::Empty));
+        assert_eq!(parse("fly 3", &state), Err(ParseError::UnknownCommand("fly".to_string())));
+        assert_eq!(parse("build Castle 2", &state), Err(ParseError::UnknownBuilding("Castle".to_string())));
+        assert_eq!(parse("build SolarPark", &state), Err(ParseError::MissingArgument("tile")));
+        assert_eq!(parse("terraform three", &state), Err(ParseError::InvalidNumber("three".to_string())));
+        assert_eq!(parse("connect 6 8 9", &state), Err(ParseError::UnexpectedArgument("9".to_string())));
+        assert_eq!(parse("1x", &state), Err(ParseError::InvalidNumber("1x".to_string())));
+        assert_eq!(
+            parse(&choices.to_string(), &state),
+            Err(ParseError::ChoiceOutOfRange { choice: choices, choices })
+        );
+    }
+}
@@ -1,6 +1,20 @@
+pub mod action_space;
 pub mod ai;
 pub mod buildings;
+pub mod env;
+pub mod evaluation;
+pub mod features;
 pub mod game_state;
+pub mod history;
+pub mod map_generator;
+pub mod map_layout;
+pub mod mcts;
 pub mod reinforcement_ai;
+pub mod replay;
 pub mod resources;
+pub mod ruleset;
+pub mod save;
 pub mod tile;
+pub mod training;
+pub mod transposition_table;
+pub mod zobrist;
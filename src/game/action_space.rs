@@ -0,0 +1,206 @@
+use crate::game::buildings::Building;
+use crate::game::game_state::Action;
+use crate::game::game_state::Action::{Build, BuildInfrastructure, Terraform};
+use crate::game::map_layout::MapLayout;
+use strum::{EnumCount, IntoEnumIterator};
+
+/// Every building except `Empty`, which is the last one.
+pub(crate) const BUILDINGS: usize = Building::COUNT - 1;
+
+/// Numbers every action of a layout, legal or not: first a build of every building on
+/// every tile, then a road along every pair of neighbours, then a terraform of every tile.
+/// Unlike positions in `legal_actions` the numbers don't change between turns.
+///
+/// Every `MapLayout` caches its own, see `MapLayout::action_space`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ActionSpace {
+    tiles: usize,
+    /// Index of the first road from each tile, relative to the first road, and the road count last.
+    road_offsets: Vec<usize>,
+    roads: Vec<(usize, usize)>,
+}
+
+impl ActionSpace {
+    pub(crate) fn new(neighbours: &[Vec<usize>]) -> ActionSpace {
+        let mut road_offsets = Vec::with_capacity(neighbours.len() + 1);
+        let mut roads = Vec::new();
+        for (from, tiles) in neighbours.iter().enumerate() {
+            road_offsets.push(roads.len());
+            roads.extend(tiles.iter().map(|&to| (from, to)));
+        }
+        road_offsets.push(roads.len());
+        ActionSpace {
+            tiles: neighbours.len(),
+            road_offsets,
+            roads,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tiles * BUILDINGS + self.roads.len() + self.tiles
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every road in index order.
+    pub fn roads(&self) -> &[(usize, usize)] {
+        &self.roads
+    }
+
+    /// `None` for actions that are not on this map, like roads between tiles that aren't neighbours.
+    pub fn index(&self, action: Action) -> Option<usize> {
+        let roads = self.tiles * BUILDINGS;
+        match action {
+            Build(building, tile) if building != Building::Empty && tile < self.tiles => {
+                Some(tile * BUILDINGS + building as usize)
+            }
+            BuildInfrastructure(from, to) if from < self.tiles => {
+                let first = self.road_offsets[from];
+                let position = self.roads[first..self.road_offsets[from + 1]].iter().position(|&(_, n)| n == to)?;
+                Some(roads + first + position)
+            }
+            Terraform(tile) if tile < self.tiles => Some(roads + self.roads.len() + tile),
+            _ => None,
+        }
+    }
+
+    pub fn action(&self, index: usize) -> Option<Action> {
+        let roads = self.tiles * BUILDINGS;
+        if index < roads {
+            let building = Building::iter().nth(index % BUILDINGS)?;
+            return Some(Build(building, index / BUILDINGS));
+        }
+        if let Some(&(from, to)) = self.roads.get(index - roads) {
+            return Some(BuildInfrastructure(from, to));
+        }
+        let tile = index - roads - self.roads.len();
+        (tile < self.tiles).then_some(Terraform(tile))
+    }
+}
+
+/// Stable numbers for actions, see `ActionSpace`.
+impl Action {
+    /// Size of the action space of `layout`.
+    pub fn count(layout: &MapLayout) -> usize {
+        layout.action_space().len()
+    }
+
+    pub fn to_index(self, layout: &MapLayout) -> Option<usize> {
+        layout.action_space().index(self)
+    }
+
+    pub fn from_index(index: usize, layout: &MapLayout) -> Option<Action> {
+        layout.action_space().action(index)
+    }
+}
+
+/// A set of action indices, one bit each. Refilling it keeps its storage, so masks can be
+/// produced every turn without allocating.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ActionMask {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl ActionMask {
+    /// An empty mask over the action space of `layout`.
+    pub fn new(layout: &MapLayout) -> ActionMask {
+        let mut mask = ActionMask::default();
+        mask.reset(Action::count(layout));
+        mask
+    }
+
+    /// Clears every bit and resizes the mask to `len` actions.
+    pub fn reset(&mut self, len: usize) {
+        self.words.clear();
+        self.words.resize(len.div_ceil(64), 0);
+        self.len = len;
+    }
+
+    /// Size of the action space, not the number of actions in the mask.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, index: usize) {
+        assert!(index < self.len, "action {index} is outside of a mask of {}", self.len);
+        self.words[index / 64] |= 1 << (index % 64);
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        if index < self.len {
+            self.words[index / 64] &= !(1 << (index % 64));
+        }
+    }
+
+    pub fn contains(&self, index: usize) -> bool {
+        index < self.len && self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// The indices in the mask in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut rest = word;
+            std::iter::from_fn(move || {
+                (rest != 0).then(|| {
+                    let bit = rest.trailing_zeros() as usize;
+                    rest &= rest - 1;
+                    i * 64 + bit
+                })
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::game_state::GameState;
+
+    #[test]
+    fn action_indices_round_trip() {
+        for layout in [MapLayout::classic(), MapLayout::hex(2).unwrap(), MapLayout::rectangle(4, 3).unwrap()] {
+            let count = Action::count(&layout);
+            for index in 0..count {
+                let action = Action::from_index(index, &layout).unwrap();
+                assert_eq!(action.to_index(&layout), Some(index), "{action:?} on {}", layout.name);
+            }
+            assert_eq!(Action::from_index(count, &layout), None);
+        }
+
+        let classic = MapLayout::classic();
+        assert_eq!(Action::count(&classic), 13 * BUILDINGS + 36 + 13);
+        assert_eq!(BuildInfrastructure(6, 0).to_index(&classic), None);
+        assert_eq!(Build(Building::Empty, 6).to_index(&classic), None);
+        assert_eq!(Terraform(13).to_index(&classic), None);
+    }
+
+    #[test]
+    fn legal_mask_matches_legal_actions() {
+        let mut mask = ActionMask::default();
+        let mut state = GameState::initialize_with_seed(9);
+        for _ in 0..30 {
+            state.legal_action_mask(&mut mask);
+            assert_eq!(mask.len(), Action::count(state.layout()));
+            let mut expected: Vec<usize> =
+                state.legal_actions.iter().map(|action| action.to_index(state.layout()).unwrap()).collect();
+            expected.sort();
+            assert_eq!(mask.iter().collect::<Vec<_>>(), expected);
+            assert_eq!(mask.count_ones(), state.legal_actions.len());
+            let Some(&action) = state.legal_actions.last() else {
+                break;
+            };
+            state.advance(action);
+        }
+    }
+}
@@ -1,114 +1,462 @@
 use crate::game::buildings::Building;
+use crate::game::evaluation::{CappedResources, Evaluator};
 use crate::game::game_state::Action::BuildInfrastructure;
-use crate::game::game_state::Status::{Loss, Win};
+use crate::game::game_state::Status::Running;
 use crate::game::game_state::{Action, GameState};
+use crate::game::tile::{Landscape, Tile};
+use crate::game::transposition_table::{Entry, TranspositionTable, DEFAULT_CAPACITY};
 use rayon::prelude::*;
-use rustc_hash::{FxHashMap, FxHasher};
-use std::cmp::min;
-use std::hash::{Hash, Hasher};
-//use crate::transposition_table;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use strum::IntoEnumIterator;
 
 pub fn evaluate_gamestate(state: &GameState) -> i16 {
-    match state {
-        GameState { status: Win, .. } => 1000,
-        GameState { status: Loss, .. } => -1000,
-        _ => {
-            min(15, state.resources.education_culture)
-                + min(15, state.resources.tech_economy)
-                + min(15, state.resources.sustainability)
+    CappedResources.evaluate(state)
+}
+pub fn search_best_move(depth: u16, state: &GameState) -> (i16, Action, Vec<Action>) {
+    Searcher::default().search_best_move(depth, state)
+}
+
+pub fn search_best_move_timed(state: &GameState, budget: Duration) -> SearchReport {
+    Searcher::default().search_best_move_timed(state, budget)
+}
+
+const MAX_DEPTH: u16 = 64;
+const NODES_BETWEEN_CLOCK_CHECKS: u64 = 1024;
+
+#[derive(Clone, Debug)]
+pub struct DepthStats {
+    pub depth: u16,
+    pub nodes: u64,
+    pub eval: i16,
+    pub principal_variation: Vec<Action>,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchReport {
+    pub eval: i16,
+    pub best_move: Action,
+    pub principal_variation: Vec<Action>,
+    pub depths: Vec<DepthStats>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SearchConfig {
+    /// More than one thread splits the root moves across a rayon pool.
+    pub threads: usize,
+    /// Also split the replies to every root move, gives better load balancing
+    /// when there are fewer root moves than threads.
+    pub split_second_ply: bool,
+    pub table_capacity: usize,
+    pub infrastructure: InfrastructurePolicy,
+}
+
+impl Default for SearchConfig {
+    fn default() -> SearchConfig {
+        SearchConfig {
+            threads: 1,
+            split_second_ply: false,
+            table_capacity: DEFAULT_CAPACITY,
+            infrastructure: InfrastructurePolicy::Ranked { max_moves: 2 },
         }
     }
 }
-pub fn search_best_move(depth: u16, state: &GameState) -> (i16, Action) {
-    let mut actions: &Vec<Action> = &state
-        .legal_actions
-        .clone()
-        .into_iter()
-        .filter(|&a| match a {
-            BuildInfrastructure(_, _) => false,
-            _ => true,
+
+/// Which `BuildInfrastructure` moves the search looks at. They are always ordered
+/// after the other moves, so on equal evals the search prefers not to expand.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InfrastructurePolicy {
+    /// Only expand when there is nothing else to do.
+    OnlyWhenForced,
+    /// Keep the `max_moves` connections that unlock the most, see `infrastructure_score`.
+    Ranked { max_moves: usize },
+    All,
+}
+
+/// Owns the transposition table so repeated positions are only searched once,
+/// across all searches made with the same searcher.
+pub struct Searcher {
+    pub table: TranspositionTable,
+    config: SearchConfig,
+    evaluator: Arc<dyn Evaluator>,
+    pool: Option<ThreadPool>,
+    /// One searcher per pool thread, each with its own table.
+    workers: Vec<Mutex<Searcher>>,
+    nodes: u64,
+    deadline: Option<Instant>,
+    aborted: bool,
+}
+
+/// Eval, line and nodes searched, `None` if the deadline passed.
+type JobResult = Option<(i16, Vec<Action>, u64)>;
+
+struct RootJob {
+    root_index: usize,
+    line: Vec<Action>,
+    state: GameState,
+    depth: u16,
+}
+
+impl Default for Searcher {
+    fn default() -> Searcher {
+        Searcher::with_config(SearchConfig::default())
+    }
+}
+
+impl Searcher {
+    pub fn new(table_capacity: usize) -> Searcher {
+        Searcher::with_config(SearchConfig {
+            table_capacity,
+            ..SearchConfig::default()
         })
-        .collect();
-    if actions.is_empty() {
-        actions = &state.legal_actions;
     }
 
-    let max_eval = actions
-        .iter()
-        .map(|action| unsafe {
+    pub fn with_config(config: SearchConfig) -> Searcher {
+        Searcher::with_evaluator(config, Arc::new(CappedResources))
+    }
+
+    /// With several threads the table capacity is split evenly between the workers. Zero threads
+    /// count as one, and if the thread pool can't be started the search stays sequential.
+    pub fn with_evaluator(config: SearchConfig, evaluator: Arc<dyn Evaluator>) -> Searcher {
+        let pool = if config.threads > 1 {
+            ThreadPoolBuilder::new().num_threads(config.threads).build().ok()
+        } else {
+            None
+        };
+        let config = SearchConfig {
+            threads: if pool.is_some() { config.threads } else { 1 },
+            ..config
+        };
+        let table_capacity = config.table_capacity / config.threads;
+        let worker_config = SearchConfig {
+            threads: 1,
+            table_capacity,
+            ..config
+        };
+        let workers = if pool.is_some() {
+            (0..config.threads)
+                .map(|_| Mutex::new(Searcher::with_evaluator(worker_config, evaluator.clone())))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Searcher {
+            table: TranspositionTable::new(table_capacity),
+            config,
+            evaluator,
+            pool,
+            workers,
+            nodes: 0,
+            deadline: None,
+            aborted: false,
+        }
+    }
+
+    /// Returns the eval, the best move and the principal variation starting with that move.
+    pub fn search_best_move(&mut self, depth: u16, state: &GameState) -> (i16, Action, Vec<Action>) {
+        self.deadline = None;
+        self.aborted = false;
+        let (eval, principal_variation) = self.search_root(depth, state).unwrap();
+        let best_move = principal_variation.first().copied().unwrap_or(Action::Terraform(420));
+        (eval, best_move, principal_variation)
+    }
+
+    /// Iterative deepening until the budget runs out. Depth 1 always completes,
+    /// every deeper iteration that gets interrupted is thrown away.
+    pub fn search_best_move_timed(&mut self, state: &GameState, budget: Duration) -> SearchReport {
+        let start = Instant::now();
+        self.deadline = None;
+        self.aborted = false;
+        let mut depths: Vec<DepthStats> = Vec::new();
+
+        for depth in 1..=MAX_DEPTH {
+            self.nodes = 0;
+            let Some((eval, principal_variation)) = self.search_root(depth, state) else {
+                break;
+            };
+            depths.push(DepthStats {
+                depth,
+                nodes: self.nodes,
+                eval,
+                principal_variation,
+                elapsed: start.elapsed(),
+            });
+            self.deadline = Some(start + budget);
+            if state.legal_actions.is_empty() || start.elapsed() >= budget {
+                break;
+            }
+        }
+
+        let deepest = depths.last().unwrap();
+        SearchReport {
+            eval: deepest.eval,
+            best_move: deepest.principal_variation.first().copied().unwrap_or(Action::Terraform(420)),
+            principal_variation: deepest.principal_variation.clone(),
+            depths,
+        }
+    }
+
+    /// Returns `None` if the deadline passed before the search finished.
+    fn search_root(&mut self, depth: u16, state: &GameState) -> Option<(i16, Vec<Action>)> {
+        if self.pool.is_some() && depth > 1 {
+            return self.search_root_parallel(depth, state);
+        }
+        self.nodes += 1;
+        let mut principal_variation = Vec::new();
+        let eval = self.search_children(depth, state, &mut principal_variation);
+        if self.aborted {
+            return None;
+        }
+        Some((eval, principal_variation))
+    }
+
+    /// Job `n` is always searched by worker `n % threads`, in job order, so every worker's
+    /// table sees the same positions on every run. The results are combined in the sequential
+    /// move order, so eval and best move are the same as with a single thread.
+    fn search_root_parallel(&mut self, depth: u16, state: &GameState) -> Option<(i16, Vec<Action>)> {
+        let root_actions = self.ordered_actions(state);
+        let mut jobs = Vec::new();
+        for (root_index, &action) in root_actions.iter().enumerate() {
+            let mut child = state.clone();
+            child.advance(action);
+            let replies = if self.config.split_second_ply && depth > 2 {
+                self.ordered_actions(&child)
+            } else {
+                Vec::new()
+            };
+            if replies.is_empty() {
+                jobs.push(RootJob {
+                    root_index,
+                    line: vec![action],
+                    state: child,
+                    depth: depth - 1,
+                });
+                continue;
+            }
+            for reply in replies {
+                let mut grandchild = child.clone();
+                grandchild.advance(reply);
+                jobs.push(RootJob {
+                    root_index,
+                    line: vec![action, reply],
+                    state: grandchild,
+                    depth: depth - 2,
+                });
+            }
+        }
+
+        let deadline = self.deadline;
+        let workers = &self.workers;
+        let per_worker: Vec<Vec<JobResult>> = self.pool.as_ref().unwrap().install(|| {
+            workers
+                .par_iter()
+                .enumerate()
+                .map(|(index, worker)| {
+                    let mut worker = worker.lock().unwrap();
+                    worker.deadline = deadline;
+                    worker.aborted = false;
+                    jobs.iter()
+                        .skip(index)
+                        .step_by(workers.len())
+                        .map(|job| {
+                            worker.nodes = 0;
+                            let mut child_line = Vec::new();
+                            let eval = worker.search_best_move_recursive(job.depth, &job.state, &mut child_line);
+                            if worker.aborted {
+                                return None;
+                            }
+                            let mut line = job.line.clone();
+                            line.append(&mut child_line);
+                            Some((eval, line, worker.nodes))
+                        })
+                        .collect()
+                })
+                .collect()
+        });
+        let workers = per_worker.len();
+        let mut per_worker: Vec<_> = per_worker.into_iter().map(Vec::into_iter).collect();
+        let results = (0..jobs.len()).map(|job| per_worker[job % workers].next().unwrap());
+
+        self.nodes += 1 + root_actions.len() as u64;
+        let mut root_results: Vec<Option<(i16, Vec<Action>)>> = vec![None; root_actions.len()];
+        for (job, result) in jobs.iter().zip(results) {
+            let (eval, line, nodes) = result?;
+            self.nodes += nodes;
+            let root_result = &mut root_results[job.root_index];
+            if root_result.as_ref().is_none_or(|(best_eval, _)| eval > *best_eval) {
+                *root_result = Some((eval, line));
+            }
+        }
+
+        let mut best: Option<(i16, Vec<Action>)> = None;
+        for (eval, line) in root_results.into_iter().flatten() {
+            // Lets the next iteration order the replies like the sequential search would
+            if line.len() > 1 {
+                let mut child = state.clone();
+                child.advance(line[0]);
+                self.table.insert(Entry {
+                    key: child.key(),
+                    depth: depth - 1,
+                    eval,
+                    best_action: Some(line[1]),
+                });
+            }
+            if best.as_ref().is_none_or(|(best_eval, _)| eval > *best_eval) {
+                best = Some((eval, line));
+            }
+        }
+        let (eval, principal_variation) = best.unwrap_or((-1000, Vec::new()));
+        self.table.insert(Entry {
+            key: state.key(),
+            depth,
+            eval,
+            best_action: principal_variation.first().copied(),
+        });
+        Some((eval, principal_variation))
+    }
+
+    /// Searches `state` to `depth` plies and writes the expected line of play into `line`.
+    pub fn search_best_move_recursive(&mut self, depth: u16, state: &GameState, line: &mut Vec<Action>) -> i16 {
+        self.nodes += 1;
+        line.clear();
+        if depth == 0 || state.status != Running {
+            return self.evaluator.evaluate(state);
+        }
+        if self.out_of_time() {
+            return 0;
+        }
+
+        // Check if the evaluation is already cached
+        if let Some(entry) = self.table.get(state.key()) {
+            if entry.depth >= depth {
+                let eval = entry.eval;
+                line.extend(self.principal_variation(state, depth));
+                return eval;
+            }
+        }
+
+        self.search_children(depth, state, line)
+    }
+
+    fn search_children(&mut self, depth: u16, state: &GameState, line: &mut Vec<Action>) -> i16 {
+        let mut max_eval = -1000;
+        let mut best_action = None;
+        let mut child_line = Vec::new();
+        for action in self.ordered_actions(state) {
             let mut new_state = state.clone();
-            new_state.advance(*action);
-            let eval = search_best_move_recursive(depth - 1, &new_state);
-            (eval, action.clone())
-        })
-        .max_by(|(eval, _), (eval2, _)| eval.cmp(eval2))
-        .unwrap_or((-1000, Action::Terraform(420)));
+            new_state.advance(action);
+            let eval = self.search_best_move_recursive(depth - 1, &new_state, &mut child_line);
+            if self.aborted {
+                return 0;
+            }
+            if best_action.is_none() || eval > max_eval {
+                max_eval = eval;
+                best_action = Some(action);
+                line.clear();
+                line.push(action);
+                line.append(&mut child_line);
+            }
+        }
 
-    max_eval
-}
+        self.table.insert(Entry {
+            key: state.key(),
+            depth,
+            eval: max_eval,
+            best_action,
+        });
+        max_eval
+    }
 
-pub fn search_best_move_recursive(depth: u16, state: &GameState) -> i16 {
-    if depth == 0 {
-        return evaluate_gamestate(state);
+    /// Follows the best actions stored in the table, stops early if an entry got replaced.
+    /// Only used below cached positions, everywhere else the line comes from the search itself.
+    pub fn principal_variation(&self, state: &GameState, depth: u16) -> Vec<Action> {
+        let mut variation = Vec::new();
+        let mut state = state.clone();
+        while variation.len() < depth as usize {
+            let Some(action) = self.table.get(state.key()).and_then(|e| e.best_action) else {
+                break;
+            };
+            // Guards against key collisions, the table never holds illegal moves otherwise
+            if !state.legal_actions.contains(&action) {
+                break;
+            }
+            variation.push(action);
+            state.advance(action);
+        }
+        variation
     }
 
-    // Check if the evaluation is already cached
-    /*let state_hash = hash_state(state);
-    if let Some(table) = unsafe { transposition_table.as_mut() } {
-        table.get(&state_hash);
-    } else {
-        unreachable!()
-    }*/
+    /// The move stored by an earlier, shallower search is tried first, ties are
+    /// resolved in favour of the earlier move so the variation stays stable.
+    fn ordered_actions(&self, state: &GameState) -> Vec<Action> {
+        let mut actions = candidate_actions(state, self.config.infrastructure);
+        let previous_best = self.table.get(state.key()).and_then(|e| e.best_action);
+        if let Some(index) = previous_best.and_then(|best| actions.iter().position(|&a| a == best)) {
+            actions[..=index].rotate_right(1);
+        }
+        actions
+    }
 
-    let mut actions: &Vec<Action> = &state
-        .legal_actions
-        .clone()
-        .into_iter()
-        .filter(|&a| match a {
-            BuildInfrastructure(_, _) => false,
-            _ => true,
-        })
-        .collect();
-    if actions.is_empty() {
-        actions = &state.legal_actions;
+    fn out_of_time(&mut self) -> bool {
+        if let Some(deadline) = self.deadline {
+            if self.nodes.is_multiple_of(NODES_BETWEEN_CLOCK_CHECKS) && Instant::now() >= deadline {
+                self.aborted = true;
+            }
+        }
+        self.aborted
     }
+}
 
-    let max_eval = actions
+fn candidate_actions(state: &GameState, policy: InfrastructurePolicy) -> Vec<Action> {
+    let (mut actions, mut infrastructure): (Vec<Action>, Vec<Action>) = state
+        .legal_actions
         .iter()
-        .map(|action| {
-            let mut new_state = state.clone();
-            new_state.advance(*action);
-            search_best_move_recursive(depth - 1, &new_state)
-        })
-        .max()
-        .unwrap_or(-1000);
-
-    /*if let Some(table) = unsafe { transposition_table.as_mut() } {
-        table.insert(state_hash, max_eval);
-    } else {
-        unreachable!()
-    }*/
-    max_eval
+        .partition(|&&a| !matches!(a, BuildInfrastructure(_, _)));
+
+    let max_moves = match policy {
+        InfrastructurePolicy::OnlyWhenForced if actions.is_empty() => infrastructure.len(),
+        InfrastructurePolicy::OnlyWhenForced => 0,
+        InfrastructurePolicy::Ranked { max_moves } => max_moves.max(actions.is_empty() as usize),
+        InfrastructurePolicy::All => infrastructure.len(),
+    };
+    if max_moves > 0 {
+        // Stable sort, equally ranked connections stay in legal move order
+        infrastructure.sort_by_cached_key(|&a| std::cmp::Reverse(infrastructure_score(state, a)));
+        actions.extend(infrastructure.into_iter().take(max_moves));
+    }
+    actions
 }
 
-fn hash_state(state: &GameState) -> u64 {
-    let mut hasher = FxHasher::default();
-    state
+/// Ranks a connection by what it unlocks: every building that can be placed on the
+/// new tile but on none of the usable ones is worth 10, every other building that fits
+/// and every unexplored neighbour of the new tile 1. Science requirements are ignored,
+/// they are met later on.
+pub fn infrastructure_score(state: &GameState, action: Action) -> i16 {
+    let BuildInfrastructure(_, to) = action else {
+        return 0;
+    };
+    let placeable = |landscape: Landscape| {
+        Building::iter()
+            .filter(|&building| state.rules().can_build(building, &Tile::empty(landscape)))
+            .fold(0u32, |mask, building| mask | 1 << building as u32)
+    };
+    let reachable = state
         .tiles
         .iter()
-        .map(|t| t.usable)
-        .collect::<Vec<bool>>()
-        .hash(&mut hasher);
-    state
-        .tiles
+        .filter(|t| t.usable)
+        .fold(0u32, |mask, t| mask | placeable(t.landscape));
+    let target = placeable(state.tiles[to].landscape);
+    let unlocked = (target & !reachable).count_ones() as i16;
+    let already_reachable = (target & reachable).count_ones() as i16;
+    let frontier = state
+        .layout()
+        .neighbours(to)
         .iter()
-        .map(|t| t.spaces)
-        .collect::<Vec<[Building; 3]>>()
-        .hash(&mut hasher);
-    state.resources.hash(&mut hasher);
-    state.season.hash(&mut hasher);
-    state.doom_timer.hash(&mut hasher);
-    hasher.finish()
+        .filter(|&&next| !state.tiles[next].usable)
+        .count() as i16;
+    10 * unlocked + already_reachable + frontier
 }
 
 #[cfg(test)]
@@ -118,15 +466,138 @@ mod tests {
     use test::Bencher;
 
     use super::*;
+    use crate::game::evaluation::WeightedLinear;
     #[bench]
     fn bench_search_best_move(b: &mut Bencher) {
-        let state = GameState::initialize();
+        let state = GameState::initialize_with_seed(1);
 
         b.iter(|| {
-            test::black_box(unsafe {
-                transposition_table = Some(FxHashMap::default());
-                let (eval, best_move) = search_best_move(5, &state.clone());
-            });
+            test::black_box(Searcher::default().search_best_move(5, &state.clone()));
+        });
+    }
+
+    fn search_without_table(depth: u16, state: &GameState) -> i16 {
+        if depth == 0 {
+            return evaluate_gamestate(state);
+        }
+        candidate_actions(state, SearchConfig::default().infrastructure)
+            .iter()
+            .map(|action| {
+                let mut new_state = state.clone();
+                new_state.advance(*action);
+                search_without_table(depth - 1, &new_state)
+            })
+            .max()
+            .unwrap_or(-1000)
+    }
+
+    #[test]
+    fn transposition_table_does_not_change_eval() {
+        let state = GameState::initialize_with_seed(1);
+        let mut searcher = Searcher::new(1 << 12);
+        let (eval, best_move, _) = searcher.search_best_move(3, &state);
+
+        let mut after_best_move = state.clone();
+        after_best_move.advance(best_move);
+        assert_eq!(eval, search_without_table(2, &after_best_move));
+        assert_eq!(eval, searcher.search_best_move(3, &state).0);
+    }
+
+    #[test]
+    fn timed_search_reports_every_completed_depth() {
+        let state = GameState::initialize_with_seed(1);
+        let report = search_best_move_timed(&state, Duration::from_millis(200));
+
+        assert!(!report.depths.is_empty());
+        for (index, stats) in report.depths.iter().enumerate() {
+            assert_eq!(stats.depth as usize, index + 1);
+            assert_eq!(stats.eval, search_best_move(stats.depth, &state).0);
+        }
+        assert_eq!(report.best_move, report.principal_variation[0]);
+    }
+
+    #[test]
+    fn searcher_uses_its_evaluator() {
+        let state = GameState::initialize_with_seed(1);
+        let weights = WeightedLinear {
+            tech_economy: 2.0,
+            ..WeightedLinear::default()
+        };
+        let mut searcher = Searcher::with_evaluator(SearchConfig::default(), Arc::new(weights));
+        let (eval, _, principal_variation) = searcher.search_best_move(3, &state);
+
+        let mut end_state = state.clone();
+        principal_variation.iter().for_each(|&action| end_state.advance(action));
+        assert_eq!(eval, weights.evaluate(&end_state));
+    }
+
+    #[test]
+    fn infrastructure_is_ranked_by_what_it_unlocks() {
+        let mut state = GameState::initialize_with_seed(1);
+        for tile in [4, 5, 6, 7, 8, 10] {
+            state.tiles[tile].landscape = Landscape::Mountain;
+        }
+        state.tiles[2].landscape = Landscape::Desert;
+        state.refresh_key();
+
+        let desert = infrastructure_score(&state, BuildInfrastructure(6, 2));
+        let mountain = infrastructure_score(&state, BuildInfrastructure(6, 4));
+        assert!(desert >= 20 && mountain < 10);
+
+        let actions = candidate_actions(&state, InfrastructurePolicy::Ranked { max_moves: 1 });
+        let infrastructure: Vec<&Action> = actions.iter().filter(|a| matches!(a, BuildInfrastructure(_, _))).collect();
+        assert_eq!(infrastructure, vec![&BuildInfrastructure(6, 2)]);
+        assert!(candidate_actions(&state, InfrastructurePolicy::OnlyWhenForced)
+            .iter()
+            .all(|a| !matches!(a, BuildInfrastructure(_, _))));
+    }
+
+    #[test]
+    fn parallel_search_matches_sequential() {
+        for seed in [1, 4, 17] {
+            let state = GameState::initialize_with_seed(seed);
+            for depth in 1..=5 {
+                let (eval, best_move, sequential_variation) = search_best_move(depth, &state);
+
+                for split_second_ply in [false, true] {
+                    let mut searcher = Searcher::with_config(SearchConfig {
+                        threads: 4,
+                        split_second_ply,
+                        table_capacity: 1 << 16,
+                        ..SearchConfig::default()
+                    });
+                    let (parallel_eval, parallel_best_move, principal_variation) =
+                        searcher.search_best_move(depth, &state);
+                    let context = format!("seed {seed}, depth {depth}, split {split_second_ply}");
+                    assert_eq!((parallel_eval, parallel_best_move), (eval, best_move), "{context}");
+                    assert_eq!(principal_variation, sequential_variation, "{context}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn zero_threads_search_sequentially() {
+        let state = GameState::initialize_with_seed(1);
+        let mut searcher = Searcher::with_config(SearchConfig {
+            threads: 0,
+            ..SearchConfig::default()
         });
+        assert_eq!(searcher.search_best_move(3, &state), search_best_move(3, &state));
+    }
+
+    #[test]
+    fn principal_variation_reaches_its_eval() {
+        let state = GameState::initialize_with_seed(1);
+        let (eval, best_move, principal_variation) = search_best_move(4, &state);
+
+        assert_eq!(principal_variation.len(), 4);
+        assert_eq!(principal_variation[0], best_move);
+        let mut end_state = state.clone();
+        for &action in &principal_variation {
+            assert!(end_state.legal_actions.contains(&action));
+            end_state.advance(action);
+        }
+        assert_eq!(evaluate_gamestate(&end_state), eval);
     }
 }
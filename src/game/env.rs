@@ -0,0 +1,296 @@
+use crate::game::action_space::{ActionMask, ActionSpace, BUILDINGS};
+use crate::game::buildings::Building;
+use crate::game::game_state::Status::Running;
+use crate::game::game_state::{GameState, IllegalAction, Season, Status};
+use crate::game::map_layout::{MapLayout, LANDSCAPE_ORDER};
+use crate::game::reinforcement_ai::RewardShaping;
+use crate::game::ruleset::Ruleset;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+use strum::IntoEnumIterator;
+
+const SEASONS: [Season; 4] = [Season::Spring, Season::Summer, Season::Autumn, Season::Winter];
+/// Resources, one-hot season and the doom timer.
+const GLOBAL_FEATURES: usize = 5 + SEASONS.len() + 1;
+/// One-hot landscape, usable and one flag per building.
+const TILE_FEATURES: usize = LANDSCAPE_ORDER.len() + 1 + BUILDINGS;
+
+/// What an agent sees of a state.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    /// Resources, season, doom timer and then every tile, see `TerraEnv::observation_size`.
+    pub values: Vec<f32>,
+    /// One flag per action of the action space, set for the legal ones.
+    pub action_mask: Vec<bool>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f64,
+    pub done: bool,
+    pub info: StepInfo,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepInfo {
+    pub status: Status,
+    pub turns: u32,
+    pub seed: u64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EnvError {
+    NotReset,
+    UnknownAction(usize),
+    Illegal(IllegalAction),
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvError::NotReset => write!(f, "reset the environment before stepping"),
+            EnvError::UnknownAction(index) => write!(f, "there is no action {index}"),
+            EnvError::Illegal(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl Error for EnvError {}
+
+/// The game as a reinforcement learning environment with a fixed-size observation and
+/// action space, for frameworks outside of Rust. `serve` speaks it over JSON lines.
+pub struct TerraEnv {
+    rules: Arc<Ruleset>,
+    layout: Arc<MapLayout>,
+    shaping: RewardShaping,
+    state: Option<GameState>,
+    /// Reused for every observation.
+    mask: ActionMask,
+    turns: u32,
+}
+
+impl TerraEnv {
+    pub fn new(rules: Arc<Ruleset>, layout: Arc<MapLayout>, shaping: RewardShaping) -> TerraEnv {
+        TerraEnv {
+            mask: ActionMask::new(&layout),
+            rules,
+            layout,
+            shaping,
+            state: None,
+            turns: 0,
+        }
+    }
+
+    pub fn action_space(&self) -> &ActionSpace {
+        self.layout.action_space()
+    }
+
+    pub fn observation_size(&self) -> usize {
+        GLOBAL_FEATURES + self.layout.len() * TILE_FEATURES + self.action_space().roads().len()
+    }
+
+    pub fn state(&self) -> Option<&GameState> {
+        self.state.as_ref()
+    }
+
+    /// Starts a new game on the board dealt by `seed`.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.state = Some(GameState::initialize_with_layout(seed, self.rules.clone(), self.layout.clone()));
+        self.turns = 0;
+        self.observe()
+    }
+
+    /// Plays the action with the given index, illegal actions leave the game as it was.
+    pub fn step(&mut self, action_index: usize) -> Result<Step, EnvError> {
+        let action = self.action_space().action(action_index).ok_or(EnvError::UnknownAction(action_index))?;
+        let state = self.state.as_mut().ok_or(EnvError::NotReset)?;
+        state.try_advance(action).map_err(EnvError::Illegal)?;
+        self.turns += 1;
+
+        let observation = self.observe();
+        let state = self.state.as_ref().unwrap();
+        Ok(Step {
+            observation,
+            reward: self.shaping.reward(state, self.turns),
+            done: state.status != Running || state.legal_actions.is_empty(),
+            info: StepInfo {
+                status: state.status,
+                turns: self.turns,
+                seed: state.seed,
+            },
+        })
+    }
+
+    /// Sees the current state, which must have been reset.
+    fn observe(&mut self) -> Observation {
+        let state = self.state.as_ref().unwrap();
+        let mut values = Vec::with_capacity(self.observation_size());
+        let resources = &state.resources;
+        values.extend(
+            [
+                resources.instant_co2,
+                resources.tech_economy,
+                resources.sustainability,
+                resources.education_culture,
+                resources.yearly_co2,
+            ]
+            .map(f32::from),
+        );
+        values.extend(SEASONS.map(|season| flag(state.season == season)));
+        values.push(state.doom_timer as f32);
+        for tile in &state.tiles {
+            values.extend(LANDSCAPE_ORDER.map(|landscape| flag(tile.landscape == landscape)));
+            values.push(flag(tile.usable));
+            values.extend(Building::iter().take(BUILDINGS).map(|b| flag(tile.spaces.contains(&b))));
+        }
+        let roads = self.layout.action_space().roads();
+        values.extend(roads.iter().map(|&(from, to)| flag(state.tiles[from].connections.contains(to))));
+
+        state.legal_action_mask(&mut self.mask);
+        let action_mask = (0..self.mask.len()).map(|index| self.mask.contains(index)).collect();
+        Observation { values, action_mask }
+    }
+}
+
+fn flag(set: bool) -> f32 {
+    if set {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// One request per line, e.g. `{"command": "reset", "seed": 3}`, `{"command": "step", "action": 12}`
+/// or `{"command": "spec"}`. Every request gets exactly one JSON line back, `{"error": ...}` if it failed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    Reset {
+        #[serde(default)]
+        seed: Option<u64>,
+    },
+    Step {
+        action: usize,
+    },
+    Spec,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Response {
+    Step(Step),
+    Reset(Observation),
+    Spec {
+        observation_size: usize,
+        action_count: usize,
+    },
+    Error {
+        error: String,
+    },
+}
+
+impl TerraEnv {
+    pub fn handle(&mut self, request: Request) -> Response {
+        match request {
+            Request::Reset { seed } => Response::Reset(self.reset(seed.unwrap_or_else(rand::random))),
+            Request::Step { action } => match self.step(action) {
+                Ok(step) => Response::Step(step),
+                Err(error) => Response::Error {
+                    error: error.to_string(),
+                },
+            },
+            Request::Spec => Response::Spec {
+                observation_size: self.observation_size(),
+                action_count: self.action_space().len(),
+            },
+        }
+    }
+}
+
+/// Answers requests from `input` on `output` until `input` ends.
+pub fn serve(env: &mut TerraEnv, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => env.handle(request),
+            Err(error) => Response::Error {
+                error: format!("bad request: {error}"),
+            },
+        };
+        serde_json::to_writer(&mut output, &response)?;
+        writeln!(output)?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classic_env() -> TerraEnv {
+        TerraEnv::new(Ruleset::shared_default(), MapLayout::shared_classic(), RewardShaping::default())
+    }
+
+    #[test]
+    fn steps_follow_the_mask() {
+        let mut env = classic_env();
+        assert_eq!(env.step(0), Err(EnvError::NotReset));
+        let mut observation = env.reset(4);
+        assert_eq!(observation.values.len(), env.observation_size());
+        assert_eq!(observation.action_mask.len(), env.action_space().len());
+
+        for _ in 0..200 {
+            let legal = observation.action_mask.iter().filter(|&&legal| legal).count();
+            assert_eq!(legal, env.state().unwrap().legal_actions.len());
+            let Some(index) = observation.action_mask.iter().position(|&legal| legal) else {
+                break;
+            };
+            let step = env.step(index).unwrap();
+            assert_eq!(step.observation.values.len(), env.observation_size());
+            observation = step.observation;
+            if step.done {
+                assert!(observation.action_mask.iter().all(|&legal| !legal));
+                break;
+            }
+        }
+
+        let illegal = observation.action_mask.iter().position(|&legal| !legal).unwrap();
+        assert!(matches!(env.step(illegal), Err(EnvError::Illegal(_))));
+        assert_eq!(env.step(usize::MAX), Err(EnvError::UnknownAction(usize::MAX)));
+    }
+
+    #[test]
+    fn json_lines_protocol() {
+        let mut env = classic_env();
+        let input = [
+            r#"{"command": "spec"}"#,
+            r#"{"command": "reset", "seed": 4}"#,
+            "",
+            "nonsense",
+            r#"{"command": "step", "action": 0}"#,
+        ]
+        .join("\n");
+        let mut output = Vec::new();
+        serve(&mut env, input.as_bytes(), &mut output).unwrap();
+        let lines: Vec<serde_json::Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0]["action_count"], env.action_space().len());
+        assert_eq!(lines[1]["values"].as_array().unwrap().len(), env.observation_size());
+        assert!(lines[2]["error"].as_str().unwrap().starts_with("bad request"));
+        assert_eq!(lines[3]["error"], "tile 0 is not connected yet");
+    }
+}
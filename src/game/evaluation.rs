@@ -0,0 +1,169 @@
+use crate::game::game_state::GameState;
+use crate::game::game_state::Status::{Loss, Running, Win};
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+pub const WIN_EVAL: i16 = 1000;
+pub const LOSS_EVAL: i16 = -1000;
+
+/// Scores a position for the search, higher is better for the player.
+pub trait Evaluator: Send + Sync {
+    fn evaluate(&self, state: &GameState) -> i16;
+}
+
+/// Sum of the three goal resources, each capped at the win target of the ruleset.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CappedResources;
+
+impl Evaluator for CappedResources {
+    fn evaluate(&self, state: &GameState) -> i16 {
+        match state.status {
+            Win => WIN_EVAL,
+            Loss => LOSS_EVAL,
+            Running => capped_goal_resources(state),
+        }
+    }
+}
+
+/// `CappedResources` minus a penalty for CO2. The doom timer part grows
+/// quadratically, so the last seasons before a loss weigh the most.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Co2Aware {
+    pub instant_co2_penalty: f64,
+    pub yearly_co2_penalty: f64,
+    pub doom_timer_penalty: f64,
+}
+
+impl Default for Co2Aware {
+    fn default() -> Co2Aware {
+        Co2Aware {
+            instant_co2_penalty: 0.5,
+            yearly_co2_penalty: 2.0,
+            doom_timer_penalty: 3.0,
+        }
+    }
+}
+
+impl Evaluator for Co2Aware {
+    fn evaluate(&self, state: &GameState) -> i16 {
+        match state.status {
+            Win => WIN_EVAL,
+            Loss => LOSS_EVAL,
+            Running => {
+                let penalty = self.instant_co2_penalty * state.resources.instant_co2.max(0) as f64
+                    + self.yearly_co2_penalty * state.resources.yearly_co2.max(0) as f64
+                    + self.doom_timer_penalty * (state.doom_timer as f64).powi(2);
+                let eval = capped_goal_resources(state) as f64 - penalty.round();
+                eval.clamp((LOSS_EVAL + 1) as f64, (WIN_EVAL - 1) as f64) as i16
+            }
+        }
+    }
+}
+
+/// Weighted sum over the resources and the doom timer, the weights are usually loaded from JSON.
+/// Missing weights default to those of `CappedResources`.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeightedLinear {
+    pub instant_co2: f64,
+    pub tech_economy: f64,
+    pub sustainability: f64,
+    pub education_culture: f64,
+    pub yearly_co2: f64,
+    pub doom_timer: f64,
+    /// Goal resources are capped at this value before weighting, `None` disables the cap.
+    pub goal_cap: Option<i16>,
+}
+
+impl Default for WeightedLinear {
+    fn default() -> WeightedLinear {
+        WeightedLinear {
+            instant_co2: 0.0,
+            tech_economy: 1.0,
+            sustainability: 1.0,
+            education_culture: 1.0,
+            yearly_co2: 0.0,
+            doom_timer: 0.0,
+            goal_cap: Some(15),
+        }
+    }
+}
+
+impl WeightedLinear {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<WeightedLinear> {
+        Ok(serde_json::from_reader(File::open(path)?)?)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<WeightedLinear> {
+        serde_json::from_str(json)
+    }
+}
+
+impl Evaluator for WeightedLinear {
+    fn evaluate(&self, state: &GameState) -> i16 {
+        match state.status {
+            Win => WIN_EVAL,
+            Loss => LOSS_EVAL,
+            Running => {
+                let cap = |value: i16| self.goal_cap.map_or(value, |cap| min(cap, value)) as f64;
+                let resources = &state.resources;
+                let eval = self.instant_co2 * resources.instant_co2 as f64
+                    + self.tech_economy * cap(resources.tech_economy)
+                    + self.sustainability * cap(resources.sustainability)
+                    + self.education_culture * cap(resources.education_culture)
+                    + self.yearly_co2 * resources.yearly_co2 as f64
+                    + self.doom_timer * state.doom_timer as f64;
+                eval.round().clamp((LOSS_EVAL + 1) as f64, (WIN_EVAL - 1) as f64) as i16
+            }
+        }
+    }
+}
+
+fn capped_goal_resources(state: &GameState) -> i16 {
+    let target = state.rules().win_target;
+    min(target, state.resources.education_culture)
+        + min(target, state.resources.tech_economy)
+        + min(target, state.resources.sustainability)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::resources::Resources;
+
+    #[test]
+    fn default_weights_match_capped_resources() {
+        let mut state = GameState::initialize_with_seed(1);
+        state.resources = Resources::new(12, 20, 4, 7, 3);
+        assert_eq!(WeightedLinear::default().evaluate(&state), CappedResources.evaluate(&state));
+        assert_eq!(WeightedLinear::from_json("{}").unwrap(), WeightedLinear::default());
+    }
+
+    #[test]
+    fn co2_aware_penalises_emissions() {
+        let mut state = GameState::initialize_with_seed(1);
+        state.resources = Resources::new(0, 5, 5, 5, 0);
+        let clean = Co2Aware::default().evaluate(&state);
+        state.resources = Resources::new(10, 5, 5, 5, 2);
+        state.doom_timer = 2;
+        assert_eq!(clean, CappedResources.evaluate(&state));
+        assert!(Co2Aware::default().evaluate(&state) < clean);
+
+        let harsh = Co2Aware {
+            doom_timer_penalty: 1000.0,
+            ..Co2Aware::default()
+        };
+        assert_eq!(harsh.evaluate(&state), LOSS_EVAL + 1);
+    }
+
+    #[test]
+    fn weights_load_from_json() {
+        let weights = WeightedLinear::from_json(r#"{"instant_co2": -1.0, "goal_cap": null}"#).unwrap();
+        let mut state = GameState::initialize_with_seed(1);
+        state.resources = Resources::new(4, 20, 1, 1, 0);
+        assert_eq!(weights.evaluate(&state), 18);
+    }
+}
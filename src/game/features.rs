@@ -0,0 +1,154 @@
+use crate::game::buildings::Building::Empty;
+use crate::game::game_state::{Action, GameState, Season, Status};
+use crate::game::tile::{Tile, MAX_TILES};
+use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
+
+/// Resources are counted in steps of this size.
+pub const RESOURCE_BUCKET: i16 = 4;
+/// Buckets above this, or below its negation, are merged into it.
+pub const MAX_BUCKET: i8 = 6;
+const LANDSCAPES: usize = 6;
+
+/// A coarse view of a `GameState` for tabular learning. Two states with the same features
+/// share their learned values, which the whole state with its exact resources and road
+/// matrix practically never does.
+///
+/// The legal actions of the state it was taken from and the reward `FeatureAgent` shapes for
+/// it are carried along for rurel, they are not part of the key and not saved.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StateFeatures {
+    /// Instant CO2, tech/economy, sustainability, education/culture and yearly CO2 in buckets.
+    pub resources: [i8; 5],
+    pub season: Season,
+    pub doom_timer: u8,
+    pub status: Status,
+    /// Buildings on tiles of each landscape, indexed by `Landscape as usize`. Big layouts
+    /// can hold more than 255 on one landscape.
+    pub buildings: [u16; LANDSCAPES],
+    /// Bit `n` is set if tile `n` is usable.
+    pub usable: u128,
+    #[serde(skip)]
+    pub(crate) legal_actions: Vec<Action>,
+    #[serde(skip)]
+    pub(crate) reward: f64,
+}
+
+impl StateFeatures {
+    pub fn of(state: &GameState) -> StateFeatures {
+        let resources = &state.resources;
+        let bucket = |value: i16| value.div_euclid(RESOURCE_BUCKET).clamp(-MAX_BUCKET as i16, MAX_BUCKET as i16) as i8;
+        let mut buildings = [0; LANDSCAPES];
+        let mut usable = 0;
+        for (index, tile) in state.tiles.iter().enumerate().take(MAX_TILES) {
+            buildings[tile.landscape as usize] += distinct_buildings(tile) as u16;
+            usable |= (tile.usable as u128) << index;
+        }
+        StateFeatures {
+            resources: [
+                bucket(resources.instant_co2),
+                bucket(resources.tech_economy),
+                bucket(resources.sustainability),
+                bucket(resources.education_culture),
+                bucket(resources.yearly_co2),
+            ],
+            season: state.season,
+            doom_timer: state.doom_timer,
+            status: state.status,
+            buildings,
+            usable,
+            legal_actions: state.legal_actions.clone(),
+            reward: 0.0,
+        }
+    }
+
+    fn key(&self) -> ([i8; 5], Season, u8, Status, [u16; LANDSCAPES], u128) {
+        (
+            self.resources,
+            self.season,
+            self.doom_timer,
+            self.status,
+            self.buildings,
+            self.usable,
+        )
+    }
+}
+
+/// Multi-space buildings fill more than one space but count once.
+fn distinct_buildings(tile: &Tile) -> u8 {
+    let spaces = &tile.spaces;
+    (0..spaces.len())
+        .filter(|&i| spaces[i] != Empty && !spaces[..i].contains(&spaces[i]))
+        .count() as u8
+}
+
+impl PartialEq for StateFeatures {
+    fn eq(&self, other: &StateFeatures) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for StateFeatures {}
+
+impl Hash for StateFeatures {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::buildings::Building::{Factory, NationalPark, School};
+    use crate::game::game_state::Action::Build;
+    use crate::game::map_layout::MapLayout;
+    use crate::game::ruleset::Ruleset;
+    use crate::game::tile::Landscape::Plains;
+    use rurel::mdp::State;
+    use std::sync::Arc;
+
+    #[test]
+    fn similar_states_share_features() {
+        let state = GameState::initialize_with_seed(5);
+        let mut other = GameState::initialize_with_seed(6);
+        assert_eq!(StateFeatures::of(&state), StateFeatures::of(&other));
+
+        other.resources.tech_economy = 1;
+        assert_eq!(StateFeatures::of(&state), StateFeatures::of(&other));
+        other.resources.tech_economy = RESOURCE_BUCKET;
+        assert_ne!(StateFeatures::of(&state), StateFeatures::of(&other));
+        other.resources.tech_economy = 1000;
+        assert_eq!(StateFeatures::of(&other).resources[1], MAX_BUCKET);
+    }
+
+    #[test]
+    fn features_count_buildings_and_usable_tiles() {
+        let mut tile = Tile::empty(Plains);
+        tile.usable = true;
+        tile.build(Factory);
+        tile.build(NationalPark);
+        assert_eq!(distinct_buildings(&tile), 2);
+
+        let mut state = GameState::initialize_with_seed(5);
+        state.advance(Build(School, 6));
+        let features = StateFeatures::of(&state);
+        assert_eq!(features.buildings[state.tiles[6].landscape as usize], 1);
+        assert_eq!(features.buildings.iter().sum::<u16>(), 1);
+        assert_eq!(features.usable, 1 << 6);
+        assert_eq!(features.actions(), state.legal_actions);
+
+        let layout = Arc::new(MapLayout::rectangle(16, 8).unwrap());
+        let mut crowded = GameState::initialize_with_layout(5, Ruleset::shared_default(), layout);
+        for tile in &mut crowded.tiles {
+            tile.landscape = Plains;
+            tile.build(Factory);
+            tile.build(School);
+        }
+        assert_eq!(StateFeatures::of(&crowded).buildings[Plains as usize], 2 * 128);
+
+        let json = serde_json::to_string(&features).unwrap();
+        let loaded: StateFeatures = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, features);
+        assert!(loaded.actions().is_empty());
+    }
+}
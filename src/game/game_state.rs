@@ -1,27 +1,82 @@
+use crate::game::action_space::ActionMask;
 use crate::game::buildings::Building;
 use crate::game::game_state::Action::{Build, Terraform};
 use crate::game::game_state::Season::Spring;
 use crate::game::game_state::Status::{Loss, Running, Win};
+use crate::game::map_layout::MapLayout;
 use crate::game::resources::Resources;
+use crate::game::ruleset::Ruleset;
 use crate::game::tile::Landscape::*;
-use crate::game::tile::{filter_actual_connections, Landscape, Tile, POSSIBLE_CONNECTIONS};
-use rand::prelude::IteratorRandom;
+use crate::game::tile::{filter_actual_connections, Tile};
+use crate::game::zobrist::ZOBRIST;
+use rand::prelude::{IteratorRandom, StdRng};
+use rand::SeedableRng;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
 use strum::IntoEnumIterator;
 use Action::BuildInfrastructure;
 use Season::{Autumn, Summer, Winter};
 
-const MAP_SIZE: usize = 13;
-
 #[derive(Clone, Hash, PartialEq, Debug, Serialize, Deserialize, Eq)]
+#[serde(from = "GameStateFile")]
 pub struct GameState {
-    pub tiles: [Tile; MAP_SIZE],
+    pub tiles: Vec<Tile>,
     pub resources: Resources,
     pub doom_timer: u8,
     pub season: Season,
     pub legal_actions: Vec<Action>,
     pub status: Status,
+    /// The seed the map was dealt from.
+    pub seed: u64,
+    /// When set, actions that would push a spent resource below zero are not legal.
+    require_affordable: bool,
+    /// Not saved with the state, saves and replays only store the ruleset id.
+    #[serde(skip)]
+    rules: Arc<Ruleset>,
+    /// Saved with the state unless it is the classic board, other layouts can't be rebuilt from a name.
+    #[serde(skip_serializing_if = "MapLayout::is_classic")]
+    layout: Arc<MapLayout>,
+    /// Derived from the rest of the state, recomputed when a state is loaded.
+    #[serde(skip)]
+    key: u64,
+}
+
+/// What a saved state contains, older saves lack the seed, the affordability flag and the layout.
+#[derive(Deserialize)]
+struct GameStateFile {
+    tiles: Vec<Tile>,
+    resources: Resources,
+    doom_timer: u8,
+    season: Season,
+    legal_actions: Vec<Action>,
+    status: Status,
+    #[serde(default)]
+    seed: u64,
+    #[serde(default)]
+    require_affordable: bool,
+    #[serde(default = "MapLayout::shared_classic")]
+    layout: Arc<MapLayout>,
+}
+
+impl From<GameStateFile> for GameState {
+    fn from(file: GameStateFile) -> GameState {
+        let mut state = GameState {
+            tiles: file.tiles,
+            resources: file.resources,
+            doom_timer: file.doom_timer,
+            season: file.season,
+            legal_actions: file.legal_actions,
+            status: file.status,
+            seed: file.seed,
+            require_affordable: file.require_affordable,
+            rules: Ruleset::shared_default(),
+            layout: file.layout,
+            key: 0,
+        };
+        state.refresh_key();
+        state
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Hash, Serialize, Deserialize, Eq)]
@@ -37,6 +92,32 @@ pub enum Action {
     Terraform(usize),
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IllegalAction {
+    GameOver(Status),
+    TileOutOfRange(usize),
+    TileNotUsable(usize),
+    SlotFull(usize),
+    /// Contains the resources the player would end up with.
+    Unaffordable(Resources),
+    NotLegal(Action),
+}
+
+impl fmt::Display for IllegalAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IllegalAction::GameOver(status) => write!(f, "the game is over ({status:?})"),
+            IllegalAction::TileOutOfRange(tile) => write!(f, "there is no tile {tile}"),
+            IllegalAction::TileNotUsable(tile) => write!(f, "tile {tile} is not connected yet"),
+            IllegalAction::SlotFull(tile) => write!(f, "tile {tile} has not enough free spaces"),
+            IllegalAction::Unaffordable(resources) => write!(f, "not enough resources, would end up with {resources:?}"),
+            IllegalAction::NotLegal(action) => write!(f, "{action:?} is not a legal action"),
+        }
+    }
+}
+
+impl std::error::Error for IllegalAction {}
+
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Serialize, Deserialize, Eq)]
 #[repr(u8)]
 pub enum Season {
@@ -48,32 +129,188 @@ pub enum Season {
 
 impl GameState {
     pub fn initialize() -> GameState {
-        let mut rng = rand::thread_rng();
-        let mut tileset = create_tileset();
-        let mut tiles: [Tile; MAP_SIZE] = [Tile::empty(Plains); 13];
+        GameState::initialize_with_seed(rand::random())
+    }
 
-        for i in 0..tiles.len() {
-            if i == 6 {
+    /// Deals the map from a seeded rng, the same seed always gives the same board.
+    pub fn initialize_with_seed(seed: u64) -> GameState {
+        GameState::initialize_with_rules(seed, Ruleset::shared_default())
+    }
+
+    pub fn initialize_with_rules(seed: u64, rules: Arc<Ruleset>) -> GameState {
+        GameState::initialize_with_layout(seed, rules, MapLayout::shared_classic())
+    }
+
+    /// Start tiles are plains, the other tiles are dealt from the layout's tileset.
+    pub fn initialize_with_layout(seed: u64, rules: Arc<Ruleset>, layout: Arc<MapLayout>) -> GameState {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut tileset = layout.tileset().to_vec();
+        let mut tiles = vec![Tile::empty(Plains); layout.len()];
+
+        for (i, tile) in tiles.iter_mut().enumerate() {
+            if layout.start_tiles().contains(&i) {
+                tile.usable = true;
                 continue;
             }
-            let (&landscape, &mut mut number_left) = tileset
+            let (landscape, number_left) = tileset
                 .iter_mut()
-                .filter(|(_, &mut number_left)| number_left > 0)
+                .filter(|(_, number_left)| *number_left > 0)
                 .choose(&mut rng)
                 .unwrap();
-            number_left -= 1;
-            tiles[i] = Tile::empty(landscape);
+            *number_left -= 1;
+            *tile = Tile::empty(*landscape);
         }
-        tiles[6].usable = true;
 
-        GameState {
-            tiles,
+        let mut state = GameState {
             resources: Resources::new(0, 0, 0, 0, 0),
             doom_timer: 0,
-            legal_actions: find_legal_actions(&tiles, 0),
+            legal_actions: find_legal_actions(&tiles, &layout, 0, &rules),
+            tiles,
             season: Spring,
             status: Running,
+            seed,
+            require_affordable: false,
+            rules,
+            layout,
+            key: 0,
+        };
+        state.key = state.compute_key();
+        state
+    }
+
+    /// Zobrist key of the position, kept up to date by `advance`.
+    pub fn key(&self) -> u64 {
+        self.key
+    }
+
+    /// Recomputes the key from scratch, needed after mutating the public fields directly.
+    pub fn refresh_key(&mut self) {
+        self.key = self.compute_key();
+    }
+
+    pub fn compute_key(&self) -> u64 {
+        let tiles = self
+            .tiles
+            .iter()
+            .enumerate()
+            .fold(0, |key, (index, tile)| key ^ ZOBRIST.tile(index, tile));
+        tiles
+            ^ ZOBRIST.resources(&self.resources)
+            ^ ZOBRIST.season(self.season)
+            ^ ZOBRIST.status(self.status)
+            ^ ZOBRIST.doom_timer(self.doom_timer)
+    }
+
+    pub fn rules(&self) -> &Arc<Ruleset> {
+        &self.rules
+    }
+
+    pub fn layout(&self) -> &Arc<MapLayout> {
+        &self.layout
+    }
+
+    /// Switches to other rules, the board and resources stay as they are.
+    pub fn set_rules(&mut self, rules: Arc<Ruleset>) {
+        self.rules = rules;
+        self.legal_actions = self.compute_legal_actions();
+    }
+
+    pub fn require_affordable(&self) -> bool {
+        self.require_affordable
+    }
+
+    pub fn set_require_affordable(&mut self, require_affordable: bool) {
+        self.require_affordable = require_affordable;
+        self.legal_actions = self.compute_legal_actions();
+    }
+
+    /// Like `advance`, but leaves the state untouched and explains why if the action can't be taken.
+    pub fn try_advance(&mut self, action: Action) -> Result<(), IllegalAction> {
+        self.validate(action)?;
+        self.advance(action);
+        Ok(())
+    }
+
+    pub fn validate(&self, action: Action) -> Result<(), IllegalAction> {
+        if self.status != Running {
+            return Err(IllegalAction::GameOver(self.status));
         }
+        let (tile, other_tile) = match action {
+            Build(_, tile) | Terraform(tile) => (tile, tile),
+            BuildInfrastructure(from, to) => (from, to),
+        };
+        if let Some(out_of_range) = [tile, other_tile].into_iter().find(|&t| t >= self.tiles.len()) {
+            return Err(IllegalAction::TileOutOfRange(out_of_range));
+        }
+        if !self.tiles[tile].usable {
+            return Err(IllegalAction::TileNotUsable(tile));
+        }
+        if let Build(building, tile) = action {
+            if self.tiles[tile].spaces_left < building.slots() {
+                return Err(IllegalAction::SlotFull(tile));
+            }
+        }
+        if self.require_affordable && !self.is_affordable(action) {
+            let mut resources = self.resources;
+            resources += self.action_cost(action);
+            return Err(IllegalAction::Unaffordable(resources));
+        }
+        if !self.legal_actions.contains(&action) {
+            return Err(IllegalAction::NotLegal(action));
+        }
+        Ok(())
+    }
+
+    pub fn action_cost(&self, action: Action) -> Resources {
+        match action {
+            Build(building, tile) => self.rules.cost(building, &self.tiles[tile]),
+            BuildInfrastructure(_, _) => self.rules.infrastructure_cost,
+            Terraform(_) => self.rules.terraform_cost,
+        }
+    }
+
+    /// An action is affordable if it doesn't take tech/economy, sustainability or
+    /// education/culture below zero. Resources it doesn't spend are not checked.
+    pub fn is_affordable(&self, action: Action) -> bool {
+        let cost = self.action_cost(action);
+        [
+            (cost.tech_economy, self.resources.tech_economy),
+            (cost.sustainability, self.resources.sustainability),
+            (cost.education_culture, self.resources.education_culture),
+        ]
+        .iter()
+        .all(|&(cost, current)| cost >= 0 || current + cost >= 0)
+    }
+
+    /// Empty once the game is won or lost, so finished games are terminal states.
+    fn compute_legal_actions(&self) -> Vec<Action> {
+        if self.status != Running {
+            return Vec::new();
+        }
+        let mut actions = find_legal_actions(&self.tiles, &self.layout, self.resources.education_culture, &self.rules);
+        if self.require_affordable {
+            actions.retain(|&action| self.is_affordable(action));
+        }
+        actions
+    }
+
+    /// `legal_actions` as a mask over `Action::to_index`, filled without allocating once `mask`
+    /// has grown to the size of the action space.
+    pub fn legal_action_mask(&self, mask: &mut ActionMask) {
+        if self.status != Running {
+            mask.reset(Action::count(&self.layout));
+            return;
+        }
+        let space = self.layout.action_space();
+        mask.reset(space.len());
+        let science = self.resources.education_culture;
+        for_each_legal_action(&self.tiles, &self.layout, science, &self.rules, |action| {
+            if !self.require_affordable || self.is_affordable(action) {
+                if let Some(index) = space.index(action) {
+                    mask.insert(index);
+                }
+            }
+        });
     }
 
     pub fn advance(&mut self, action: Action) {
@@ -86,95 +323,134 @@ impl GameState {
         self.check_loss_condition();
         self.check_win_condition();
         self.advance_season();
-        self.legal_actions = find_legal_actions(&self.tiles, self.resources.education_culture);
+        self.legal_actions = self.compute_legal_actions();
+        debug_assert_eq!(self.key, self.compute_key(), "incremental key diverged after {action:?}");
     }
 
     fn build(&mut self, building: Building, tile_to_build_on: usize) {
+        self.key ^= ZOBRIST.tile(tile_to_build_on, &self.tiles[tile_to_build_on]);
         let tile = &mut self.tiles[tile_to_build_on];
         tile.build(building);
-        self.resources += building.cost(*tile);
+        let cost = self.rules.cost(building, tile);
+        self.key ^= ZOBRIST.tile(tile_to_build_on, &self.tiles[tile_to_build_on]);
+        self.add_resources(cost);
     }
 
     fn terraform(&mut self, tile: usize) {
+        self.key ^= ZOBRIST.tile(tile, &self.tiles[tile]);
         self.tiles[tile].terraform();
-        self.resources += Resources::new(3, 0, -3, 0, 0);
+        self.key ^= ZOBRIST.tile(tile, &self.tiles[tile]);
+        self.add_resources(self.rules.terraform_cost);
     }
 
     fn build_infrastructure(&mut self, tile_from: usize, tile_to: usize) {
+        self.key ^= ZOBRIST.tile(tile_to, &self.tiles[tile_to]);
         self.tiles[tile_from].connect(tile_to);
         self.tiles[tile_to].connect(tile_from);
         self.tiles[tile_to].usable = true;
-        self.resources += Resources::new(2, 0, -3, 0, 0);
+        self.key ^= ZOBRIST.tile(tile_to, &self.tiles[tile_to]);
+        self.add_resources(self.rules.infrastructure_cost);
+    }
+
+    fn add_resources(&mut self, resources: Resources) {
+        self.key ^= ZOBRIST.resources(&self.resources);
+        self.resources += resources;
+        self.key ^= ZOBRIST.resources(&self.resources);
     }
 
     fn advance_season(&mut self) {
+        self.key ^= ZOBRIST.season(self.season);
         match self.season {
             Spring => self.season = Summer,
             Summer => self.season = Autumn,
             Autumn => self.season = Winter,
             Winter => {
                 self.season = Spring;
-                self.resources.instant_co2 += self.resources.yearly_co2;
+                self.add_resources(Resources::new(self.resources.yearly_co2, 0, 0, 0, 0));
             }
         }
+        self.key ^= ZOBRIST.season(self.season);
+    }
+
+    fn set_status(&mut self, status: Status) {
+        self.key ^= ZOBRIST.status(self.status) ^ ZOBRIST.status(status);
+        self.status = status;
+    }
+
+    fn set_doom_timer(&mut self, doom_timer: u8) {
+        self.key ^= ZOBRIST.doom_timer(self.doom_timer) ^ ZOBRIST.doom_timer(doom_timer);
+        self.doom_timer = doom_timer;
     }
 
     fn check_loss_condition(&mut self) {
-        if self.doom_timer == 4 {
-            self.status = Loss;
-        } else if self.resources.instant_co2 >= 20 {
-            self.doom_timer += 1;
-        } else if self.resources.instant_co2 < 20 {
-            self.doom_timer = 0;
+        if self.doom_timer >= self.rules.doom_timer_limit {
+            self.set_status(Loss);
+        } else if self.resources.instant_co2 >= self.rules.co2_threshold {
+            self.set_doom_timer(self.doom_timer + 1);
+        } else {
+            self.set_doom_timer(0);
         }
     }
 
     fn check_win_condition(&mut self) {
-        if self.resources.sustainability >= 15
-            && self.resources.education_culture >= 15
-            && self.resources.tech_economy >= 15
-        {
-            self.status = Win;
+        if self.rules.is_won(&self.resources) {
+            self.set_status(Win);
         }
     }
 }
 
-pub fn find_legal_actions(tiles: &[Tile; MAP_SIZE], science: i16) -> Vec<Action> {
+pub fn find_legal_actions(tiles: &[Tile], layout: &MapLayout, science: i16, rules: &Ruleset) -> Vec<Action> {
     let mut actions = Vec::new();
+    for_each_legal_action(tiles, layout, science, rules, |action| actions.push(action));
+    actions
+}
 
+/// The actions of `find_legal_actions` as indices into `mask`, which is cleared first.
+/// Reuses the mask's storage instead of collecting them.
+pub fn find_legal_mask(
+    tiles: &[Tile],
+    layout: &MapLayout,
+    science: i16,
+    rules: &Ruleset,
+    mask: &mut ActionMask,
+) {
+    let space = layout.action_space();
+    mask.reset(space.len());
+    for_each_legal_action(tiles, layout, science, rules, |action| {
+        if let Some(index) = space.index(action) {
+            mask.insert(index);
+        }
+    });
+}
+
+fn for_each_legal_action(
+    tiles: &[Tile],
+    layout: &MapLayout,
+    science: i16,
+    rules: &Ruleset,
+    mut f: impl FnMut(Action),
+) {
     for (index, &tile) in tiles.iter().enumerate().filter(|(_, t)| t.usable) {
         // Check for terraforming actions
-        if tiles[index].landscape != Plains && tiles[index].landscape != Ocean {
-            actions.push(Terraform(index));
+        if rules.can_terraform(tiles[index].landscape) {
+            f(Terraform(index));
         }
 
         // Check for infrastructure actions
-        for &possible in &POSSIBLE_CONNECTIONS[index] {
+        for &neighbour in layout.neighbours(index) {
+            let possible = BuildInfrastructure(index, neighbour);
             if filter_actual_connections(tiles, possible) {
-                actions.push(possible);
+                f(possible);
             }
         }
 
         // Check for build actions
         for building in Building::iter() {
-            if building.can_build_on_tile(&tiles[index]) && building.has_enough_science(science) {
-                actions.push(Build(building, index));
+            if rules.can_build(building, &tiles[index]) && rules.has_enough_science(building, science) {
+                f(Build(building, index));
             }
         }
     }
-
-    actions
-}
-
-fn create_tileset() -> HashMap<Landscape, i32> {
-    let mut tileset = HashMap::new();
-    tileset.insert(Mountain, 3);
-    tileset.insert(Plains, 3);
-    tileset.insert(Forest, 3);
-    tileset.insert(Desert, 3);
-    tileset.insert(Ocean, 3);
-    tileset.insert(Swamp, 3);
-    tileset
 }
 
 #[cfg(test)]
@@ -186,16 +462,32 @@ mod tests {
     use super::*;
     #[bench]
     fn bench_find_legal_actions(b: &mut Bencher) {
-        let state = GameState::initialize();
+        let state = GameState::initialize_with_seed(1);
+
+        b.iter(|| {
+            test::black_box(find_legal_actions(
+                &state.tiles,
+                state.layout(),
+                state.resources.tech_economy,
+                state.rules(),
+            ));
+        });
+    }
+
+    #[bench]
+    fn bench_find_legal_mask(b: &mut Bencher) {
+        let state = GameState::initialize_with_seed(1);
+        let mut mask = ActionMask::new(state.layout());
 
         b.iter(|| {
-            test::black_box(find_legal_actions(&state.tiles, state.resources.tech_economy));
+            state.legal_action_mask(&mut mask);
+            test::black_box(&mask);
         });
     }
 
     #[bench]
     fn bench_advance_build(b: &mut Bencher) {
-        let state = GameState::initialize();
+        let state = GameState::initialize_with_seed(1);
         let first_build_action = *state.legal_actions.iter().find(|&&a| matches!(a, Build(_, _))).unwrap();
 
         b.iter(|| test::black_box(state.clone().advance(first_build_action)));
@@ -203,7 +495,7 @@ mod tests {
 
     #[bench]
     fn bench_advance_build_connection(b: &mut Bencher) {
-        let state = GameState::initialize();
+        let state = GameState::initialize_with_seed(1);
 
         let first_action = *state
             .legal_actions
@@ -216,9 +508,120 @@ mod tests {
         });
     }
 
+    #[test]
+    fn incremental_key_matches_recompute() {
+        let mut state = GameState::initialize_with_seed(1);
+        for turn in 0..40 {
+            let Some(&action) = state.legal_actions.get(turn * 7 % state.legal_actions.len().max(1)) else {
+                break;
+            };
+            state.advance(action);
+            assert_eq!(state.key(), state.compute_key());
+        }
+    }
+
+    #[test]
+    fn same_seed_deals_same_map() {
+        let state = GameState::initialize_with_seed(42);
+        assert_eq!(state, GameState::initialize_with_seed(42));
+        assert_eq!(state.seed, 42);
+
+        for seed in 0..20 {
+            let tiles = GameState::initialize_with_seed(seed).tiles;
+            for landscape in [Mountain, Forest, Desert, Ocean, Swamp] {
+                assert!(tiles.iter().filter(|t| t.landscape == landscape).count() <= 3);
+            }
+        }
+    }
+
+    #[test]
+    fn try_advance_rejects_illegal_actions() {
+        let mut state = GameState::initialize_with_seed(1);
+        let untouched = state.clone();
+
+        assert_eq!(state.try_advance(Terraform(13)), Err(IllegalAction::TileOutOfRange(13)));
+        assert_eq!(state.try_advance(Terraform(0)), Err(IllegalAction::TileNotUsable(0)));
+        assert_eq!(
+            state.try_advance(BuildInfrastructure(6, 0)),
+            Err(IllegalAction::NotLegal(BuildInfrastructure(6, 0)))
+        );
+        assert_eq!(state, untouched);
+
+        state.status = Loss;
+        let action = state.legal_actions[0];
+        assert_eq!(state.try_advance(action), Err(IllegalAction::GameOver(Loss)));
+    }
+
+    #[test]
+    fn affordability_rules_filter_legal_actions() {
+        let mut state = GameState::initialize_with_seed(1);
+        state.set_require_affordable(true);
+        assert!(state.require_affordable());
+
+        assert!(!state.legal_actions.contains(&Build(Building::Field, 6)));
+        assert!(!state.legal_actions.iter().any(|a| matches!(a, BuildInfrastructure(_, _))));
+        assert_eq!(
+            state.try_advance(Build(Building::Field, 6)),
+            Err(IllegalAction::Unaffordable(Resources::new(0, -1, 2, 0, 0)))
+        );
+        assert!(state.try_advance(Build(Building::Store, 6)).is_err());
+        state.try_advance(Build(Building::Museum, 6)).unwrap();
+    }
+
+    #[test]
+    fn games_on_other_layouts_stay_on_the_map() {
+        let layout = Arc::new(MapLayout::hex(3).unwrap());
+        let mut state = GameState::initialize_with_layout(8, Ruleset::shared_default(), layout.clone());
+        assert_eq!(state.tiles.len(), 37);
+        assert!(state.tiles[layout.start_tiles()[0]].usable);
+        assert_eq!(state.try_advance(Terraform(37)), Err(IllegalAction::TileOutOfRange(37)));
+
+        for turn in 0..80 {
+            let Some(&action) = state.legal_actions.get(turn * 5 % state.legal_actions.len().max(1)) else {
+                break;
+            };
+            if let BuildInfrastructure(from, to) = action {
+                assert!(layout.neighbours(from).contains(&to), "{action:?}");
+            }
+            state.try_advance(action).unwrap();
+            assert_eq!(state.key(), state.compute_key());
+        }
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.contains("hex-3"));
+        assert!(!json.contains("key"));
+        let loaded: GameState = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.layout(), &layout);
+        assert_eq!(loaded.key(), state.key());
+        assert_eq!(loaded, state);
+        assert!(!serde_json::to_string(&GameState::initialize_with_seed(1)).unwrap().contains("layout"));
+    }
+
+    #[test]
+    fn finished_games_have_no_legal_actions() {
+        let mut state = GameState::initialize_with_seed(3);
+        state.resources = Resources::new(0, 14, 15, 15, 0);
+        state.refresh_key();
+        state.advance(Build(Building::Library, 6));
+        assert_eq!(state.status, Win);
+        assert!(state.legal_actions.is_empty());
+
+        let mut state = GameState::initialize_with_seed(3);
+        state.resources = Resources::new(100, 0, 0, 0, 0);
+        state.refresh_key();
+        while state.status == Running {
+            let action = state.legal_actions[0];
+            state.advance(action);
+        }
+        assert_eq!(state.status, Loss);
+        assert!(state.legal_actions.is_empty());
+        state.set_require_affordable(true);
+        assert!(state.legal_actions.is_empty());
+    }
+
     #[bench]
     fn bench_gamestate_clone(b: &mut test::Bencher) {
-        let state = GameState::initialize();
+        let state = GameState::initialize_with_seed(1);
         b.iter(|| test::black_box(state.clone()))
     }
 }
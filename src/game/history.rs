@@ -0,0 +1,190 @@
+use crate::game::game_state::{Action, GameState, IllegalAction};
+use crate::game::ruleset::Ruleset;
+use crate::game::save::SavedGame;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A game together with the actions that were played. Going back rebuilds the
+/// state by replaying from the initial state, undone actions are kept for `redo`
+/// until a different action is played.
+#[derive(Clone, Debug)]
+pub struct Game {
+    initial_state: GameState,
+    actions: Vec<Action>,
+    turn: usize,
+    state: GameState,
+}
+
+impl Game {
+    pub fn new(initial_state: GameState) -> Game {
+        Game {
+            state: initial_state.clone(),
+            initial_state,
+            actions: Vec::new(),
+            turn: 0,
+        }
+    }
+
+    pub fn state(&self) -> &GameState {
+        &self.state
+    }
+
+    pub fn initial_state(&self) -> &GameState {
+        &self.initial_state
+    }
+
+    /// The actions leading to the current state, without the ones that were undone.
+    pub fn history(&self) -> &[Action] {
+        &self.actions[..self.turn]
+    }
+
+    pub fn turn(&self) -> usize {
+        self.turn
+    }
+
+    pub fn play(&mut self, action: Action) -> Result<(), IllegalAction> {
+        self.state.try_advance(action)?;
+        self.actions.truncate(self.turn);
+        self.actions.push(action);
+        self.turn += 1;
+        Ok(())
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.turn > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.turn < self.actions.len()
+    }
+
+    /// Returns false if there was nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        if !self.can_undo() {
+            return false;
+        }
+        self.jump_to(self.turn - 1);
+        true
+    }
+
+    /// Returns false if there was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        if !self.can_redo() {
+            return false;
+        }
+        self.state.advance(self.actions[self.turn]);
+        self.turn += 1;
+        true
+    }
+
+    /// Moves to the state after `turn` actions, redo stays possible up to the last played action.
+    /// Turns past the end are clamped.
+    pub fn jump_to(&mut self, turn: usize) {
+        self.turn = turn.min(self.actions.len());
+        self.state = self.replay(self.turn);
+    }
+
+    /// Rebuilds the state after the first `turn` actions from scratch.
+    pub fn replay(&self, turn: usize) -> GameState {
+        let mut state = self.initial_state.clone();
+        self.actions[..turn.min(self.actions.len())]
+            .iter()
+            .for_each(|&action| state.advance(action));
+        state
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        SavedGame {
+            initial_state: Some(self.initial_state.clone()),
+            ..SavedGame::new(&self.state, self.history())
+        }
+        .save(path)
+    }
+
+    /// Replays the saved actions and fails if they don't lead to the saved state.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Game> {
+        Game::load_with_rules(path, Ruleset::shared_default())
+    }
+
+    pub fn load_with_rules(path: impl AsRef<Path>, rules: Arc<Ruleset>) -> io::Result<Game> {
+        let saved = SavedGame::load_with_rules(path, rules.clone())?;
+        let layout = saved.state.layout().clone();
+        let initial_state = saved
+            .initial_state
+            .unwrap_or_else(|| GameState::initialize_with_layout(saved.seed, rules, layout));
+        let mut game = Game::new(initial_state);
+        for &action in &saved.actions {
+            game.play(action)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        }
+        if game.state != saved.state {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the saved actions don't lead to the saved state",
+            ));
+        }
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play_first_actions(game: &mut Game, count: usize) {
+        for _ in 0..count {
+            let action = game.state().legal_actions[0];
+            game.play(action).unwrap();
+        }
+    }
+
+    #[test]
+    fn undo_redo_and_jump() {
+        let mut game = Game::new(GameState::initialize_with_seed(9));
+        play_first_actions(&mut game, 3);
+        let after_three = game.state().clone();
+
+        assert!(game.undo());
+        assert!(game.undo());
+        assert_eq!(game.turn(), 1);
+        assert_eq!(game.state(), &game.replay(1));
+        assert!(game.redo());
+        assert!(game.redo());
+        assert!(!game.redo());
+        assert_eq!(game.state(), &after_three);
+
+        game.jump_to(0);
+        assert_eq!(game.state(), game.initial_state());
+        assert!(!game.undo());
+        game.jump_to(10);
+        assert_eq!(game.state(), &after_three);
+    }
+
+    #[test]
+    fn playing_after_undo_drops_redo() {
+        let mut game = Game::new(GameState::initialize_with_seed(9));
+        play_first_actions(&mut game, 2);
+        game.undo();
+        let other = *game.state().legal_actions.last().unwrap();
+        game.play(other).unwrap();
+
+        assert!(!game.can_redo());
+        assert_eq!(game.history().len(), 2);
+        assert_eq!(game.history()[1], other);
+    }
+
+    #[test]
+    fn save_and_load_replays_history() {
+        let mut game = Game::new(GameState::initialize_with_seed(11));
+        play_first_actions(&mut game, 4);
+
+        let path = std::env::temp_dir().join(format!("terra2_history_round_trip_{}.json", std::process::id()));
+        game.save(&path).unwrap();
+        let loaded = Game::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.history(), game.history());
+        assert_eq!(loaded.state(), game.state());
+    }
+}
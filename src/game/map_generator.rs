@@ -0,0 +1,274 @@
+use crate::game::game_state::GameState;
+use crate::game::map_layout::{MapLayout, LANDSCAPE_ORDER};
+use crate::game::ruleset::Ruleset;
+use crate::game::tile::Landscape;
+use crate::game::tile::Landscape::{Desert, Ocean, Plains, Swamp};
+use rand::prelude::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use strum_macros::EnumString;
+
+/// Fairness lost for every road beyond the first that is needed to reach a landscape.
+const STEP_PENALTY: u32 = 10;
+/// Fairness lost for a landscape that is dealt but can't be reached at all.
+const UNREACHABLE_PENALTY: u32 = 30;
+/// Fairness lost for every pair of neighbouring tiles with the same landscape.
+const CLUMP_PENALTY: u32 = 5;
+
+/// Constraints for `generate`. The default deals the layout's tileset without any,
+/// which gives the same board as `GameState::initialize_with_layout`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratorConfig {
+    /// Replaces the layout's count for these landscapes, e.g. to deal more deserts.
+    pub landscape_counts: Option<Vec<(Landscape, u32)>>,
+    /// Desert and Ocean are each at most this many roads away from a start tile.
+    pub resources_within: Option<u32>,
+    pub no_adjacent_swamps: bool,
+    pub difficulty: Option<Difficulty>,
+    /// Boards dealt before giving up.
+    pub max_attempts: u32,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> GeneratorConfig {
+        GeneratorConfig {
+            landscape_counts: None,
+            resources_within: None,
+            no_adjacent_swamps: false,
+            difficulty: None,
+            max_attempts: 10_000,
+        }
+    }
+}
+
+/// How hard a board is, derived from its fairness score.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn of(fairness: u32) -> Difficulty {
+        match fairness {
+            75.. => Difficulty::Easy,
+            45..=74 => Difficulty::Normal,
+            _ => Difficulty::Hard,
+        }
+    }
+}
+
+/// What a dealt board looks like from the start tiles.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapReport {
+    /// Fewest roads to the nearest tile of each landscape, `None` if there is no reachable one.
+    pub distances: Vec<(Landscape, Option<u32>)>,
+    pub adjacent_swamps: u32,
+    /// 100 for a board where every dealt landscape is next to the start and no two
+    /// neighbours look the same, less for every extra road and every clump.
+    pub fairness: u32,
+}
+
+impl MapReport {
+    pub fn of(state: &GameState) -> MapReport {
+        let layout = state.layout();
+        let steps = layout.steps_from_start();
+        let distances: Vec<(Landscape, Option<u32>)> = LANDSCAPE_ORDER
+            .iter()
+            .map(|&landscape| {
+                let nearest = (0..state.tiles.len())
+                    .filter(|&tile| state.tiles[tile].landscape == landscape)
+                    .filter_map(|tile| steps[tile])
+                    .min();
+                (landscape, nearest)
+            })
+            .collect();
+
+        let pairs: Vec<(usize, usize)> = (0..layout.len())
+            .flat_map(|from| layout.neighbours(from).iter().map(move |&to| (from.min(to), from.max(to))))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let same = |landscape: Option<Landscape>| {
+            pairs
+                .iter()
+                .filter(|&&(a, b)| state.tiles[a].landscape == state.tiles[b].landscape)
+                .filter(|&&(a, _)| landscape.is_none_or(|l| state.tiles[a].landscape == l))
+                .count() as u32
+        };
+
+        let dealt = |landscape: Landscape| layout.tileset().iter().any(|&(l, count)| l == landscape && count > 0);
+        let distance_penalty: u32 = distances
+            .iter()
+            .filter(|&&(landscape, _)| landscape != Plains && dealt(landscape))
+            .map(|&(_, distance)| distance.map_or(UNREACHABLE_PENALTY, |d| d.saturating_sub(1) * STEP_PENALTY))
+            .sum();
+        MapReport {
+            adjacent_swamps: same(Some(Swamp)),
+            fairness: 100u32.saturating_sub(distance_penalty + same(None) * CLUMP_PENALTY),
+            distances,
+        }
+    }
+
+    pub fn distance(&self, landscape: Landscape) -> Option<u32> {
+        self.distances.iter().find(|&&(l, _)| l == landscape).and_then(|&(_, d)| d)
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        Difficulty::of(self.fairness)
+    }
+
+    fn satisfies(&self, config: &GeneratorConfig) -> bool {
+        let near = |landscape| match config.resources_within {
+            Some(limit) => self.distance(landscape).is_some_and(|d| d <= limit),
+            None => true,
+        };
+        near(Desert)
+            && near(Ocean)
+            && !(config.no_adjacent_swamps && self.adjacent_swamps > 0)
+            && config.difficulty.is_none_or(|difficulty| self.difficulty() == difficulty)
+    }
+}
+
+/// A board that meets the constraints. Its seed and layout deal it again.
+#[derive(Clone, Debug)]
+pub struct GeneratedMap {
+    pub state: GameState,
+    pub report: MapReport,
+    pub attempts: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GeneratorError {
+    InvalidCounts(String),
+    NoMatchingMap { attempts: u32 },
+}
+
+impl fmt::Display for GeneratorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeneratorError::InvalidCounts(reason) => write!(f, "invalid landscape counts: {reason}"),
+            GeneratorError::NoMatchingMap { attempts } => {
+                write!(f, "no board met the constraints in {attempts} attempts")
+            }
+        }
+    }
+}
+
+impl Error for GeneratorError {}
+
+/// Deals boards until one meets the constraints. The first board uses `seed` itself,
+/// the others seeds drawn from it, so the same arguments always give the same board.
+pub fn generate(
+    seed: u64,
+    rules: Arc<Ruleset>,
+    layout: Arc<MapLayout>,
+    config: &GeneratorConfig,
+) -> Result<GeneratedMap, GeneratorError> {
+    let layout = match &config.landscape_counts {
+        Some(counts) => {
+            let mut tileset = layout.tileset().to_vec();
+            for &(landscape, count) in counts {
+                match tileset.iter_mut().find(|(l, _)| *l == landscape) {
+                    Some(entry) => entry.1 = count,
+                    None => tileset.push((landscape, count)),
+                }
+            }
+            Arc::new(layout.with_tileset(tileset).map_err(GeneratorError::InvalidCounts)?)
+        }
+        None => layout,
+    };
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut candidate = seed;
+    for attempts in 1..=config.max_attempts {
+        let state = GameState::initialize_with_layout(candidate, rules.clone(), layout.clone());
+        let report = MapReport::of(&state);
+        if report.satisfies(config) {
+            return Ok(GeneratedMap {
+                state,
+                report,
+                attempts,
+            });
+        }
+        candidate = rng.random();
+    }
+    Err(GeneratorError::NoMatchingMap {
+        attempts: config.max_attempts,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::tile::Landscape::{Forest, Mountain};
+
+    fn generate_classic(seed: u64, config: &GeneratorConfig) -> Result<GeneratedMap, GeneratorError> {
+        generate(seed, Ruleset::shared_default(), MapLayout::shared_classic(), config)
+    }
+
+    #[test]
+    fn default_config_deals_the_seeded_board() {
+        let map = generate_classic(12, &GeneratorConfig::default()).unwrap();
+        assert_eq!(map.attempts, 1);
+        assert_eq!(map.state, GameState::initialize_with_seed(12));
+        assert!(map.report.fairness <= 100);
+    }
+
+    #[test]
+    fn constraints_hold_on_generated_boards() {
+        let config = GeneratorConfig {
+            resources_within: Some(1),
+            no_adjacent_swamps: true,
+            ..GeneratorConfig::default()
+        };
+        for seed in 0..10 {
+            let map = generate_classic(seed, &config).unwrap();
+            assert_eq!(map.report, MapReport::of(&map.state));
+            assert!(map.report.distance(Desert).is_some_and(|d| d <= 1));
+            assert!(map.report.distance(Ocean).is_some_and(|d| d <= 1));
+            assert_eq!(map.report.adjacent_swamps, 0);
+
+            let rules = Ruleset::shared_default();
+            let again = GameState::initialize_with_layout(map.state.seed, rules, MapLayout::shared_classic());
+            assert_eq!(again, map.state);
+        }
+
+        for difficulty in [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard] {
+            let config = GeneratorConfig {
+                difficulty: Some(difficulty),
+                ..GeneratorConfig::default()
+            };
+            assert_eq!(generate_classic(3, &config).unwrap().report.difficulty(), difficulty);
+        }
+    }
+
+    #[test]
+    fn custom_counts_override_the_tileset() {
+        let counts = vec![(Desert, 6), (Ocean, 6), (Mountain, 0), (Plains, 0), (Forest, 0), (Swamp, 0)];
+        let config = GeneratorConfig {
+            landscape_counts: Some(counts),
+            ..GeneratorConfig::default()
+        };
+        let map = generate_classic(4, &config).unwrap();
+        let deserts = map.state.tiles.iter().filter(|t| t.landscape == Desert).count();
+        assert_eq!(deserts, 6);
+        assert!(map.state.tiles.iter().all(|t| t.landscape != Mountain));
+
+        let config = GeneratorConfig {
+            landscape_counts: Some(vec![(Desert, 0), (Ocean, 0), (Mountain, 0), (Plains, 0)]),
+            ..GeneratorConfig::default()
+        };
+        assert!(matches!(generate_classic(4, &config), Err(GeneratorError::InvalidCounts(_))));
+        let config = GeneratorConfig {
+            resources_within: Some(0),
+            max_attempts: 20,
+            ..GeneratorConfig::default()
+        };
+        assert_eq!(generate_classic(4, &config).unwrap_err(), GeneratorError::NoMatchingMap { attempts: 20 });
+    }
+}
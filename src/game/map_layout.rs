@@ -0,0 +1,383 @@
+use crate::game::action_space::ActionSpace;
+use crate::game::tile::Landscape::{Desert, Forest, Mountain, Ocean, Plains, Swamp};
+use crate::game::tile::{Landscape, MAX_TILES};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+pub const CLASSIC_LAYOUT_NAME: &str = "classic";
+
+/// Order the landscapes are dealt in, kept fixed so seeded maps are reproducible.
+pub const LANDSCAPE_ORDER: [Landscape; 6] = [Mountain, Plains, Forest, Desert, Ocean, Swamp];
+
+lazy_static! {
+    static ref CLASSIC_LAYOUT: Arc<MapLayout> = Arc::new(MapLayout::classic());
+}
+
+/// The shape of a map: which tiles there are, which ones a road can be built between,
+/// where the game starts and which landscapes are dealt to the remaining tiles.
+///
+/// Positions are only used for drawing. They are (column, half row) on a flat-top hex
+/// grid, so vertical neighbours are two half rows apart and diagonal ones one column and
+/// one half row. Square grids use the same columns with rows two half rows apart.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "MapLayoutFile")]
+pub struct MapLayout {
+    pub name: String,
+    /// Roads that can be built from each tile. Usually symmetric, but it doesn't have to be.
+    neighbours: Vec<Vec<usize>>,
+    /// Usable from the start, always plains.
+    start_tiles: Vec<usize>,
+    /// How many tiles of each landscape are dealt to the tiles that are not start tiles.
+    tileset: Vec<(Landscape, u32)>,
+    positions: Vec<(i32, i32)>,
+    /// Derived from the neighbours, cached so action indices don't walk every tile.
+    #[serde(skip_serializing)]
+    action_space: ActionSpace,
+}
+
+impl MapLayout {
+    /// The board of the original game, shared so states don't each carry their own copy.
+    pub fn shared_classic() -> Arc<MapLayout> {
+        CLASSIC_LAYOUT.clone()
+    }
+
+    pub fn is_classic(layout: &Arc<MapLayout>) -> bool {
+        Arc::ptr_eq(layout, &CLASSIC_LAYOUT) || **layout == **CLASSIC_LAYOUT
+    }
+
+    /// The 13 tiles of the board game, starting in the middle.
+    pub fn classic() -> MapLayout {
+        let neighbours = vec![
+            vec![2],
+            vec![4],
+            vec![0, 4, 5, 6],
+            vec![5],
+            vec![1, 2, 6, 7],
+            vec![2, 3, 6, 8],
+            vec![2, 4, 5, 7, 8, 10],
+            vec![4, 6, 9, 10],
+            vec![4, 6, 10, 11],
+            vec![7],
+            vec![6, 7, 8, 12],
+            vec![8],
+            vec![10],
+        ];
+        MapLayout {
+            name: CLASSIC_LAYOUT_NAME.to_string(),
+            action_space: ActionSpace::new(&neighbours),
+            neighbours,
+            start_tiles: vec![6],
+            tileset: LANDSCAPE_ORDER.iter().map(|&landscape| (landscape, 3)).collect(),
+            positions: vec![
+                (0, -4),
+                (-2, -2),
+                (0, -2),
+                (2, -2),
+                (-1, -1),
+                (1, -1),
+                (0, 0),
+                (-1, 1),
+                (1, 1),
+                (-2, 2),
+                (0, 2),
+                (2, 2),
+                (0, 4),
+            ],
+        }
+    }
+
+    /// A hexagon of hexes with `radius` rings around the start tile in the middle,
+    /// tiles are numbered from the top row to the bottom one.
+    pub fn hex(radius: u32) -> Result<MapLayout, String> {
+        let radius = radius as i32;
+        let mut positions = Vec::new();
+        for q in -radius..=radius {
+            for r in (-radius).max(-q - radius)..=radius.min(-q + radius) {
+                positions.push((q, 2 * r + q));
+            }
+        }
+        positions.sort_by_key(|&(column, half_row)| (half_row, column));
+        let start = positions.iter().position(|&p| p == (0, 0)).unwrap_or(0);
+        let offsets = [(0, 2), (0, -2), (1, 1), (1, -1), (-1, 1), (-1, -1)];
+        MapLayout::from_positions(format!("hex-{radius}"), positions, &offsets, vec![start])
+    }
+
+    /// A `width` x `height` grid where roads go to the four orthogonal neighbours,
+    /// starting in the middle.
+    pub fn rectangle(width: u32, height: u32) -> Result<MapLayout, String> {
+        let (width, height) = (width as i32, height as i32);
+        let positions: Vec<(i32, i32)> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, 2 * y)))
+            .collect();
+        let start = ((height / 2) * width + width / 2) as usize;
+        let offsets = [(0, 2), (0, -2), (1, 0), (-1, 0)];
+        MapLayout::from_positions(format!("rect-{width}x{height}"), positions, &offsets, vec![start])
+    }
+
+    /// Connects every pair of tiles whose positions differ by one of the `offsets` and
+    /// deals the landscapes in even numbers.
+    fn from_positions(
+        name: String,
+        positions: Vec<(i32, i32)>,
+        offsets: &[(i32, i32)],
+        start_tiles: Vec<usize>,
+    ) -> Result<MapLayout, String> {
+        let neighbours = positions
+            .iter()
+            .map(|&(column, half_row)| {
+                offsets
+                    .iter()
+                    .filter_map(|&(dc, dr)| positions.iter().position(|&p| p == (column + dc, half_row + dr)))
+                    .collect()
+            })
+            .collect();
+        let tileset = even_tileset(positions.len().saturating_sub(start_tiles.len()));
+        MapLayout::try_from(MapLayoutFile {
+            name,
+            neighbours,
+            start_tiles,
+            tileset,
+            positions,
+        })
+    }
+
+    /// Reads `hex:<radius>`, `rect:<width>x<height>`, `classic` or the path of a layout file.
+    pub fn parse(spec: &str) -> io::Result<MapLayout> {
+        let invalid = |error: String| io::Error::new(io::ErrorKind::InvalidInput, error);
+        if spec == CLASSIC_LAYOUT_NAME {
+            return Ok(MapLayout::classic());
+        }
+        if let Some(radius) = spec.strip_prefix("hex:") {
+            let radius = radius.parse().map_err(|_| invalid(format!("{radius} is not a radius")))?;
+            return MapLayout::hex(radius).map_err(invalid);
+        }
+        if let Some(size) = spec.strip_prefix("rect:") {
+            let parsed = size
+                .split_once('x')
+                .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+            let (width, height) = parsed.ok_or_else(|| invalid(format!("{size} is not <width>x<height>")))?;
+            return MapLayout::rectangle(width, height).map_err(invalid);
+        }
+        MapLayout::load(spec)
+    }
+
+    /// Loads a `.toml` file as TOML and anything else as JSON.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<MapLayout> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|extension| extension == "toml") {
+            toml::from_str(&text).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+        } else {
+            Ok(serde_json::from_str(&text)?)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.neighbours.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.neighbours.is_empty()
+    }
+
+    pub fn neighbours(&self, tile: usize) -> &[usize] {
+        &self.neighbours[tile]
+    }
+
+    pub fn action_space(&self) -> &ActionSpace {
+        &self.action_space
+    }
+
+    pub fn start_tiles(&self) -> &[usize] {
+        &self.start_tiles
+    }
+
+    pub fn tileset(&self) -> &[(Landscape, u32)] {
+        &self.tileset
+    }
+
+    pub fn positions(&self) -> &[(i32, i32)] {
+        &self.positions
+    }
+
+    /// The same map dealing `tileset` instead, named after the original.
+    pub fn with_tileset(&self, tileset: Vec<(Landscape, u32)>) -> Result<MapLayout, String> {
+        MapLayout::try_from(MapLayoutFile {
+            name: format!("{}-custom", self.name),
+            neighbours: self.neighbours.clone(),
+            start_tiles: self.start_tiles.clone(),
+            tileset,
+            positions: self.positions.clone(),
+        })
+    }
+
+    /// How many roads it takes at least to reach each tile from a start tile, `None` if it can't be reached.
+    pub fn steps_from_start(&self) -> Vec<Option<u32>> {
+        let mut steps = vec![None; self.len()];
+        let mut queue = VecDeque::new();
+        for &start in &self.start_tiles {
+            steps[start] = Some(0);
+            queue.push_back(start);
+        }
+        while let Some(tile) = queue.pop_front() {
+            let next = steps[tile].map(|s| s + 1);
+            for &neighbour in &self.neighbours[tile] {
+                if steps[neighbour].is_none() {
+                    steps[neighbour] = next;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        steps
+    }
+}
+
+/// Only the name and size, layouts with the same name are expected to be the same.
+impl Hash for MapLayout {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.len().hash(state);
+    }
+}
+
+impl Default for MapLayout {
+    fn default() -> MapLayout {
+        MapLayout::classic()
+    }
+}
+
+/// The same number of every landscape, the first ones in `LANDSCAPE_ORDER` get the remainder.
+pub fn even_tileset(tiles: usize) -> Vec<(Landscape, u32)> {
+    LANDSCAPE_ORDER
+        .iter()
+        .enumerate()
+        .map(|(index, &landscape)| {
+            let extra = (index < tiles % LANDSCAPE_ORDER.len()) as usize;
+            (landscape, (tiles / LANDSCAPE_ORDER.len() + extra) as u32)
+        })
+        .collect()
+}
+
+/// What a layout file contains, positions are optional and default to rows of tiles.
+#[derive(Deserialize)]
+struct MapLayoutFile {
+    name: String,
+    neighbours: Vec<Vec<usize>>,
+    start_tiles: Vec<usize>,
+    tileset: Vec<(Landscape, u32)>,
+    #[serde(default)]
+    positions: Vec<(i32, i32)>,
+}
+
+impl TryFrom<MapLayoutFile> for MapLayout {
+    type Error = String;
+
+    fn try_from(file: MapLayoutFile) -> Result<MapLayout, String> {
+        let tiles = file.neighbours.len();
+        if tiles == 0 || tiles > MAX_TILES {
+            return Err(format!("a layout needs between 1 and {MAX_TILES} tiles, not {tiles}"));
+        }
+        for (tile, neighbours) in file.neighbours.iter().enumerate() {
+            if let Some(&bad) = neighbours.iter().find(|&&n| n >= tiles || n == tile) {
+                return Err(format!("tile {tile} can't have a road to tile {bad}"));
+            }
+        }
+        if file.start_tiles.is_empty() {
+            return Err("a layout needs at least one start tile".to_string());
+        }
+        if let Some(&bad) = file.start_tiles.iter().find(|&&t| t >= tiles) {
+            return Err(format!("start tile {bad} is not on the map"));
+        }
+        let mut start_tiles = file.start_tiles.clone();
+        start_tiles.sort_unstable();
+        start_tiles.dedup();
+        let dealt: u32 = file.tileset.iter().map(|&(_, count)| count).sum();
+        if (dealt as usize) < tiles - start_tiles.len() {
+            return Err(format!(
+                "the tileset has {dealt} tiles, but {} need a landscape",
+                tiles - start_tiles.len()
+            ));
+        }
+        let positions = match file.positions.len() {
+            0 => {
+                let width = (tiles as f64).sqrt().ceil() as usize;
+                (0..tiles)
+                    .map(|tile| ((tile % width) as i32, 2 * (tile / width) as i32))
+                    .collect()
+            }
+            n if n == tiles => file.positions,
+            n => return Err(format!("{n} positions for {tiles} tiles")),
+        };
+        Ok(MapLayout {
+            name: file.name,
+            action_space: ActionSpace::new(&file.neighbours),
+            neighbours: file.neighbours,
+            start_tiles,
+            tileset: file.tileset,
+            positions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_symmetric(layout: &MapLayout) -> bool {
+        (0..layout.len()).all(|tile| {
+            layout
+                .neighbours(tile)
+                .iter()
+                .all(|&n| layout.neighbours(n).contains(&tile))
+        })
+    }
+
+    #[test]
+    fn generated_layouts_have_the_expected_shape() {
+        let hex = MapLayout::hex(2).unwrap();
+        assert_eq!(hex.len(), 19);
+        assert_eq!(hex.positions()[hex.start_tiles()[0]], (0, 0));
+        assert_eq!(hex.neighbours(hex.start_tiles()[0]).len(), 6);
+        assert!(is_symmetric(&hex));
+        assert_eq!(hex.tileset().iter().map(|&(_, n)| n).sum::<u32>(), 18);
+
+        let rect = MapLayout::rectangle(5, 3).unwrap();
+        assert_eq!(rect.len(), 15);
+        assert_eq!(rect.start_tiles(), &[7]);
+        assert_eq!(rect.neighbours(0), &[5, 1]);
+        assert_eq!(rect.neighbours(7).len(), 4);
+        assert!(is_symmetric(&rect));
+
+        assert!(MapLayout::hex(7).is_err());
+
+        let steps = MapLayout::classic().steps_from_start();
+        assert_eq!(steps[6], Some(0));
+        assert_eq!(steps[2], Some(1));
+        assert_eq!(steps[0], Some(2));
+        assert_eq!(steps.iter().flatten().max(), Some(&2));
+    }
+
+    #[test]
+    fn layout_files_are_validated() {
+        let json = r#"{"name": "line", "neighbours": [[1], [0, 2], [1]], "start_tiles": [0],
+            "tileset": [["Desert", 1], ["Ocean", 1]]}"#;
+        let layout: MapLayout = serde_json::from_str(json).unwrap();
+        assert_eq!(layout.positions().len(), 3);
+
+        let json = r#"{"name": "bad", "neighbours": [[1], [5]], "start_tiles": [0], "tileset": [["Desert", 1]]}"#;
+        assert!(serde_json::from_str::<MapLayout>(json).is_err());
+        let json = r#"{"name": "bad", "neighbours": [[1], [0]], "start_tiles": [0], "tileset": []}"#;
+        assert!(serde_json::from_str::<MapLayout>(json).is_err());
+
+        let classic = serde_json::to_string(&MapLayout::classic()).unwrap();
+        assert_eq!(serde_json::from_str::<MapLayout>(&classic).unwrap(), MapLayout::classic());
+        assert_eq!(MapLayout::parse("rect:4x2").unwrap().len(), 8);
+        assert!(MapLayout::parse("hex:x").is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use crate::game::ai::evaluate_gamestate;
+use crate::game::game_state::Status::{Running, Win};
+use crate::game::game_state::{Action, GameState};
+use rand::prelude::{IndexedRandom, StdRng};
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Debug)]
+pub enum Budget {
+    Iterations(u32),
+    Time(Duration),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RolloutPolicy {
+    Random,
+    /// Takes the action with the best immediate `evaluate_gamestate`, ties are broken randomly.
+    Greedy,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct MctsConfig {
+    pub budget: Budget,
+    pub exploration: f64,
+    pub rollout_policy: RolloutPolicy,
+    /// Rollouts that have not reached Win or Loss after this many moves are scored by the heuristic.
+    pub max_rollout_length: usize,
+    pub seed: u64,
+}
+
+impl Default for MctsConfig {
+    fn default() -> MctsConfig {
+        MctsConfig {
+            budget: Budget::Iterations(10_000),
+            exploration: std::f64::consts::SQRT_2,
+            rollout_policy: RolloutPolicy::Random,
+            max_rollout_length: 200,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MctsReport {
+    pub best_move: Action,
+    /// Mean reward of the best move, 1.0 means every rollout through it was won.
+    pub value: f64,
+    pub iterations: u32,
+    /// Action, visits and mean reward for every expanded root move.
+    pub root_moves: Vec<(Action, u32, f64)>,
+}
+
+struct Node {
+    action: Option<Action>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<Action>,
+    visits: u32,
+    total_reward: f64,
+}
+
+impl Node {
+    fn new(action: Option<Action>, parent: Option<usize>, state: &GameState) -> Node {
+        Node {
+            action,
+            parent,
+            children: Vec::new(),
+            untried: if is_terminal(state) { Vec::new() } else { state.legal_actions.clone() },
+            visits: 0,
+            total_reward: 0.0,
+        }
+    }
+
+    fn mean_reward(&self) -> f64 {
+        if self.visits == 0 {
+            0.0
+        } else {
+            self.total_reward / self.visits as f64
+        }
+    }
+}
+
+pub fn search_best_move(state: &GameState, config: MctsConfig) -> MctsReport {
+    Mcts::new(config).search(state)
+}
+
+/// UCT over the single player game tree. The tree only stores actions,
+/// states are rebuilt by replaying the path from the root.
+pub struct Mcts {
+    config: MctsConfig,
+    nodes: Vec<Node>,
+    rng: StdRng,
+}
+
+impl Mcts {
+    pub fn new(config: MctsConfig) -> Mcts {
+        Mcts {
+            config,
+            nodes: Vec::new(),
+            rng: StdRng::seed_from_u64(config.seed),
+        }
+    }
+
+    pub fn search(&mut self, state: &GameState) -> MctsReport {
+        self.nodes.clear();
+        self.nodes.push(Node::new(None, None, state));
+
+        let start = Instant::now();
+        let mut iterations = 0;
+        while !self.budget_exhausted(iterations, start) {
+            self.iterate(state);
+            iterations += 1;
+        }
+
+        let root_moves: Vec<(Action, u32, f64)> = self.nodes[0]
+            .children
+            .iter()
+            .map(|&child| {
+                let node = &self.nodes[child];
+                (node.action.unwrap(), node.visits, node.mean_reward())
+            })
+            .collect();
+        let (best_move, _, value) = root_moves
+            .iter()
+            .copied()
+            .max_by_key(|&(_, visits, _)| visits)
+            .unwrap_or((Action::Terraform(420), 0, 0.0));
+
+        MctsReport {
+            best_move,
+            value,
+            iterations,
+            root_moves,
+        }
+    }
+
+    fn budget_exhausted(&self, iterations: u32, start: Instant) -> bool {
+        match self.config.budget {
+            Budget::Iterations(limit) => iterations >= limit,
+            Budget::Time(limit) => iterations > 0 && start.elapsed() >= limit,
+        }
+    }
+
+    fn iterate(&mut self, root_state: &GameState) {
+        let mut state = root_state.clone();
+
+        // Selection
+        let mut node = 0;
+        while self.nodes[node].untried.is_empty() && !self.nodes[node].children.is_empty() {
+            node = self.select_child(node);
+            state.advance(self.nodes[node].action.unwrap());
+        }
+
+        // Expansion
+        if !self.nodes[node].untried.is_empty() {
+            let untried = &mut self.nodes[node].untried;
+            let action = untried.swap_remove(self.rng.random_range(0..untried.len()));
+            state.advance(action);
+            let child = self.nodes.len();
+            self.nodes.push(Node::new(Some(action), Some(node), &state));
+            self.nodes[node].children.push(child);
+            node = child;
+        }
+
+        // Simulation
+        let reward = self.rollout(state);
+
+        // Backpropagation
+        let mut current = Some(node);
+        while let Some(index) = current {
+            self.nodes[index].visits += 1;
+            self.nodes[index].total_reward += reward;
+            current = self.nodes[index].parent;
+        }
+    }
+
+    fn select_child(&self, node: usize) -> usize {
+        let log_visits = (self.nodes[node].visits as f64).ln();
+        let uct = |child: usize| {
+            let child = &self.nodes[child];
+            child.mean_reward() + self.config.exploration * (log_visits / child.visits as f64).sqrt()
+        };
+        *self.nodes[node]
+            .children
+            .iter()
+            .max_by(|&&a, &&b| uct(a).total_cmp(&uct(b)))
+            .unwrap()
+    }
+
+    fn rollout(&mut self, mut state: GameState) -> f64 {
+        for _ in 0..self.config.max_rollout_length {
+            if is_terminal(&state) {
+                break;
+            }
+            let action = match self.config.rollout_policy {
+                RolloutPolicy::Random => *state.legal_actions.choose(&mut self.rng).unwrap(),
+                RolloutPolicy::Greedy => self.greedy_action(&state),
+            };
+            state.advance(action);
+        }
+        reward(&state)
+    }
+
+    fn greedy_action(&mut self, state: &GameState) -> Action {
+        let evals: Vec<(Action, i16)> = state
+            .legal_actions
+            .iter()
+            .map(|&action| {
+                let mut new_state = state.clone();
+                new_state.advance(action);
+                (action, evaluate_gamestate(&new_state))
+            })
+            .collect();
+        let best_eval = evals.iter().map(|&(_, eval)| eval).max().unwrap();
+        let best: Vec<Action> = evals
+            .into_iter()
+            .filter(|&(_, eval)| eval == best_eval)
+            .map(|(action, _)| action)
+            .collect();
+        *best.choose(&mut self.rng).unwrap()
+    }
+}
+
+fn is_terminal(state: &GameState) -> bool {
+    state.status != Running || state.legal_actions.is_empty()
+}
+
+/// Win is 1 and Loss or running out of moves 0. Unfinished rollouts get at most 0.5,
+/// scaled by how close the capped resources are to the win condition.
+fn reward(state: &GameState) -> f64 {
+    match state.status {
+        Win => 1.0,
+        Running if !state.legal_actions.is_empty() => {
+            let cap = (3 * state.rules().win_target).max(1);
+            evaluate_gamestate(state).clamp(0, cap) as f64 / (2 * cap) as f64
+        }
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::ruleset::Ruleset;
+    use std::sync::Arc;
+
+    #[test]
+    fn iteration_budget_is_respected_and_move_is_legal() {
+        let state = GameState::initialize_with_seed(1);
+        let report = search_best_move(
+            &state,
+            MctsConfig {
+                budget: Budget::Iterations(300),
+                ..MctsConfig::default()
+            },
+        );
+
+        assert_eq!(report.iterations, 300);
+        assert!(state.legal_actions.contains(&report.best_move));
+        assert_eq!(report.root_moves.iter().map(|&(_, visits, _)| visits).sum::<u32>(), 300);
+    }
+
+    #[test]
+    fn same_seed_gives_same_result() {
+        let state = GameState::initialize_with_seed(1);
+        let config = MctsConfig {
+            budget: Budget::Iterations(200),
+            rollout_policy: RolloutPolicy::Greedy,
+            max_rollout_length: 20,
+            seed: 7,
+            ..MctsConfig::default()
+        };
+
+        let first = search_best_move(&state, config);
+        let second = search_best_move(&state, config);
+        assert_eq!(first.best_move, second.best_move);
+        assert_eq!(first.value, second.value);
+    }
+
+    #[test]
+    fn rollout_reward_scales_with_the_win_target() {
+        let mut rules = Ruleset::default();
+        rules.id = "long-game".to_string();
+        rules.win_target = 25;
+        let mut state = GameState::initialize_with_rules(1, Arc::new(rules));
+        state.resources.sustainability = 20;
+        state.resources.education_culture = 20;
+        state.resources.tech_economy = 20;
+
+        assert!(!state.legal_actions.is_empty());
+        assert_eq!(reward(&state), 60.0 / 150.0);
+    }
+}
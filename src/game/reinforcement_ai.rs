@@ -1,11 +1,22 @@
-use crate::game::game_state::Status::{Loss, Win};
-use crate::game::game_state::{Action, GameState};
+use crate::game::evaluation::{CappedResources, Evaluator, LOSS_EVAL, WIN_EVAL};
+use crate::game::features::StateFeatures;
+use crate::game::game_state::{Action, GameState, Status};
 use rurel::mdp::{Agent, State};
-use std::cmp::min;
+use rurel::strategy::explore::ExplorationStrategy;
+use rurel::strategy::terminate::TerminationStrategy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 pub struct MyAgent {
     pub(crate) state: GameState,
 }
+
+impl MyAgent {
+    pub fn new(state: GameState) -> MyAgent {
+        MyAgent { state }
+    }
+}
+
 impl Agent<GameState> for MyAgent {
     fn current_state(&self) -> &GameState {
         &self.state
@@ -15,25 +26,206 @@ impl Agent<GameState> for MyAgent {
     }
 }
 
+/// Without a turn count every win gets the full bonus.
 impl State for GameState {
     type A = Action;
 
     fn reward(&self) -> f64 {
-        if self.legal_actions.is_empty() {
-            return -1000f64;
-        };
-        match self {
-            GameState { status: Win, .. } => 1000f64,
-            GameState { status: Loss, .. } => -1000f64,
-            _ => {
-                min(15, self.resources.education_culture) as f64
-                    + min(15, self.resources.tech_economy) as f64
-                    + min(15, self.resources.sustainability) as f64
-                //- (0.5 * self.resources.instant_co2 as f64)
+        RewardShaping::default().reward(self, 0)
+    }
+    fn actions(&self) -> Vec<Action> {
+        self.legal_actions.clone()
+    }
+}
+
+/// What an agent is rewarded with after every action.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RewardShaping {
+    /// Taken off every turn for each point of instant CO2 above zero.
+    pub co2_penalty: f64,
+    pub win_bonus: f64,
+    /// Wins within this many turns get the whole bonus, later ones `par_turns / turns` of it.
+    pub par_turns: u32,
+    /// For a loss and for running out of actions.
+    pub loss_penalty: f64,
+}
+
+impl Default for RewardShaping {
+    fn default() -> RewardShaping {
+        RewardShaping {
+            co2_penalty: 0.5,
+            win_bonus: WIN_EVAL as f64,
+            par_turns: 40,
+            loss_penalty: -LOSS_EVAL as f64,
+        }
+    }
+}
+
+impl RewardShaping {
+    /// The reward for arriving at `state` after `turns` actions.
+    pub fn reward(&self, state: &GameState, turns: u32) -> f64 {
+        match state.status {
+            Status::Win => self.win_bonus * self.par_turns.max(1) as f64 / turns.max(self.par_turns).max(1) as f64,
+            Status::Loss => -self.loss_penalty,
+            Status::Running if state.legal_actions.is_empty() => -self.loss_penalty,
+            Status::Running => {
+                let co2 = state.resources.instant_co2.max(0) as f64;
+                CappedResources.evaluate(state) as f64 - self.co2_penalty * co2
             }
         }
     }
+}
+
+/// Plays a whole game but shows rurel only its `StateFeatures`, so the Q-table is keyed by them.
+pub struct FeatureAgent {
+    pub(crate) state: GameState,
+    features: StateFeatures,
+    shaping: RewardShaping,
+    turns: u32,
+}
+
+impl FeatureAgent {
+    pub fn new(state: GameState, shaping: RewardShaping) -> FeatureAgent {
+        let mut features = StateFeatures::of(&state);
+        features.reward = shaping.reward(&state, 0);
+        FeatureAgent {
+            features,
+            state,
+            shaping,
+            turns: 0,
+        }
+    }
+}
+
+impl Agent<StateFeatures> for FeatureAgent {
+    fn current_state(&self) -> &StateFeatures {
+        &self.features
+    }
+
+    fn take_action(&mut self, action: &Action) {
+        self.state.advance(*action);
+        self.turns += 1;
+        self.features = StateFeatures::of(&self.state);
+        self.features.reward = self.shaping.reward(&self.state, self.turns);
+    }
+}
+
+/// Same actions as the state the features were taken from, the reward is set by the agent.
+impl State for StateFeatures {
+    type A = Action;
+
+    fn reward(&self) -> f64 {
+        self.reward
+    }
+
     fn actions(&self) -> Vec<Action> {
         self.legal_actions.clone()
     }
 }
+
+/// States that know whether their game is still running.
+pub trait HasStatus {
+    fn status(&self) -> Status;
+}
+
+impl HasStatus for GameState {
+    fn status(&self) -> Status {
+        self.status
+    }
+}
+
+impl HasStatus for StateFeatures {
+    fn status(&self) -> Status {
+        self.status
+    }
+}
+
+/// Takes a random action with probability `epsilon` and otherwise the one with the highest
+/// value in `values`. Actions without a value are skipped, and states without any get a random action.
+///
+/// rurel doesn't let exploration look at the values being trained, so `values` is a copy
+/// the caller refreshes, e.g. once per epoch.
+pub struct EpsilonGreedy<S: State> {
+    pub epsilon: f64,
+    pub values: HashMap<S, HashMap<S::A, f64>>,
+}
+
+impl<S: State> ExplorationStrategy<S> for EpsilonGreedy<S> {
+    fn pick_action(&self, agent: &mut dyn Agent<S>) -> S::A {
+        if rand::random::<f64>() >= self.epsilon {
+            let state = agent.current_state();
+            let best = self.values.get(state).and_then(|values| {
+                state
+                    .actions()
+                    .into_iter()
+                    .filter_map(|action| values.get(&action).map(|&value| (action, value)))
+                    .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            });
+            if let Some((action, _)) = best {
+                agent.take_action(&action);
+                return action;
+            }
+        }
+        agent.pick_random_action()
+    }
+}
+
+/// Ends an episode once the game is won or lost, after `max_steps` actions
+/// or when no action is left.
+pub struct EpisodeEnd {
+    steps: u32,
+    max_steps: u32,
+}
+
+impl EpisodeEnd {
+    pub fn new(max_steps: u32) -> EpisodeEnd {
+        EpisodeEnd { steps: 0, max_steps }
+    }
+}
+
+impl<S: State + HasStatus> TerminationStrategy<S> for EpisodeEnd {
+    fn should_stop(&mut self, state: &S) -> bool {
+        self.steps += 1;
+        state.status() != Status::Running || self.steps >= self.max_steps || state.actions().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::game_state::Status::{Loss, Win};
+
+    #[test]
+    fn rewards_follow_the_shaping() {
+        let shaping = RewardShaping {
+            co2_penalty: 2.0,
+            win_bonus: 100.0,
+            par_turns: 10,
+            loss_penalty: 50.0,
+        };
+        let mut state = GameState::initialize_with_seed(1);
+        state.resources.instant_co2 = 3;
+        assert_eq!(shaping.reward(&state, 1), CappedResources.evaluate(&state) as f64 - 6.0);
+
+        state.status = Win;
+        assert_eq!(shaping.reward(&state, 5), 100.0);
+        assert_eq!(shaping.reward(&state, 20), 50.0);
+        state.status = Loss;
+        assert_eq!(shaping.reward(&state, 5), -50.0);
+    }
+
+    #[test]
+    fn episodes_end_with_the_game() {
+        let mut end = EpisodeEnd::new(100);
+        let mut state = GameState::initialize_with_seed(1);
+        assert!(!TerminationStrategy::<GameState>::should_stop(&mut end, &state));
+        state.status = Win;
+        assert!(TerminationStrategy::<GameState>::should_stop(&mut end, &state));
+        assert!(end.should_stop(&StateFeatures::of(&state)));
+
+        let mut end = EpisodeEnd::new(2);
+        let state = GameState::initialize_with_seed(1);
+        assert!(!TerminationStrategy::<GameState>::should_stop(&mut end, &state));
+        assert!(TerminationStrategy::<GameState>::should_stop(&mut end, &state));
+    }
+}
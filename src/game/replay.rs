@@ -0,0 +1,161 @@
+use crate::game::game_state::{Action, GameState, IllegalAction};
+use crate::game::history::Game;
+use crate::game::map_layout::MapLayout;
+use crate::game::ruleset::Ruleset;
+use crate::game::save::{check_ruleset, FORMAT_VERSION};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Seed plus moves, much smaller than a save file and meant for sharing games.
+/// The board is dealt again from the seed when the replay is played.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub format_version: u32,
+    pub seed: u64,
+    pub ruleset_id: String,
+    pub moves: Vec<ReplayMove>,
+    /// The rules the board is dealt and the moves are played with, only the id is stored.
+    #[serde(skip, default = "Ruleset::shared_default")]
+    pub rules: Arc<Ruleset>,
+    /// Stored in full unless it is the classic board.
+    #[serde(default = "MapLayout::shared_classic", skip_serializing_if = "MapLayout::is_classic")]
+    pub layout: Arc<MapLayout>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplayMove {
+    pub action: Action,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<Annotation>,
+}
+
+/// What the AI thought of the position before the move was played.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub eval: i16,
+    pub best_move: Action,
+}
+
+impl Replay {
+    pub fn new(seed: u64, actions: &[Action]) -> Replay {
+        Replay::with_rules(seed, actions, Ruleset::shared_default())
+    }
+
+    pub fn with_rules(seed: u64, actions: &[Action], rules: Arc<Ruleset>) -> Replay {
+        Replay {
+            format_version: FORMAT_VERSION,
+            seed,
+            ruleset_id: rules.id.clone(),
+            rules,
+            layout: MapLayout::shared_classic(),
+            moves: actions
+                .iter()
+                .map(|&action| ReplayMove {
+                    action,
+                    annotation: None,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn from_game(game: &Game) -> Replay {
+        let initial_state = game.initial_state();
+        Replay {
+            layout: initial_state.layout().clone(),
+            ..Replay::with_rules(initial_state.seed, game.history(), initial_state.rules().clone())
+        }
+    }
+
+    /// Asks `analyse` about every position before a move and stores the answer with the move.
+    pub fn annotate(&mut self, mut analyse: impl FnMut(&GameState) -> Annotation) -> Result<(), (usize, IllegalAction)> {
+        let states = self.states()?;
+        for (replay_move, state) in self.moves.iter_mut().zip(&states) {
+            replay_move.annotation = Some(analyse(state));
+        }
+        Ok(())
+    }
+
+    /// The states from the initial board up to the one after the last move.
+    /// Fails with the index of the first move that can't be played.
+    pub fn states(&self) -> Result<Vec<GameState>, (usize, IllegalAction)> {
+        let mut state = GameState::initialize_with_layout(self.seed, self.rules.clone(), self.layout.clone());
+        let mut states = vec![state.clone()];
+        for (index, replay_move) in self.moves.iter().enumerate() {
+            state.try_advance(replay_move.action).map_err(|error| (index, error))?;
+            states.push(state.clone());
+        }
+        Ok(states)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Replay> {
+        Replay::load_with_rules(path, Ruleset::shared_default())
+    }
+
+    pub fn load_with_rules(path: impl AsRef<Path>, rules: Arc<Ruleset>) -> io::Result<Replay> {
+        let mut replay: Replay = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if replay.format_version > FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "replay format {} is newer than the supported format {}",
+                    replay.format_version, FORMAT_VERSION
+                ),
+            ));
+        }
+        check_ruleset(&replay.ruleset_id, &rules)?;
+        replay.rules = rules;
+        Ok(replay)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_reproduces_the_game() {
+        let mut game = Game::new(GameState::initialize_with_seed(21));
+        for _ in 0..5 {
+            let action = game.state().legal_actions[0];
+            game.play(action).unwrap();
+        }
+        let mut replay = Replay::from_game(&game);
+        replay
+            .annotate(|state| Annotation {
+                eval: 0,
+                best_move: state.legal_actions[0],
+            })
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!("terra2_replay_round_trip_{}.json", std::process::id()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, replay);
+        let states = loaded.states().unwrap();
+        assert_eq!(states.len(), 6);
+        assert_eq!(states.last(), Some(game.state()));
+        assert!(loaded.moves.iter().all(|m| m.annotation.map(|a| a.best_move) == Some(m.action)));
+    }
+
+    #[test]
+    fn illegal_move_is_reported_with_its_index() {
+        let mut replay = Replay::new(21, &[]);
+        replay.moves.push(ReplayMove {
+            action: Action::Terraform(0),
+            annotation: None,
+        });
+        assert_eq!(replay.states(), Err((0, IllegalAction::TileNotUsable(0))));
+    }
+}
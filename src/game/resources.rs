@@ -11,7 +11,7 @@ pub struct Resources {
 }
 
 impl Resources {
-    pub fn new(
+    pub const fn new(
         instant_co2: i16,
         tech_economy: i16,
         sustainability: i16,
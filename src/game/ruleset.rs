@@ -0,0 +1,328 @@
+use crate::game::buildings::Building;
+use crate::game::buildings::Building::*;
+use crate::game::resources::Resources;
+use crate::game::tile::Landscape::{Desert, Forest, Mountain, Ocean, Plains, Swamp};
+use crate::game::tile::{Landscape, Tile};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use strum::IntoEnumIterator;
+
+pub const DEFAULT_RULESET_ID: &str = "default";
+
+lazy_static! {
+    static ref DEFAULT_RULESET: Arc<Ruleset> = Arc::new(Ruleset::default());
+}
+
+/// Everything about the rules that designers may want to balance: what buildings cost,
+/// where they can be built, how much education they need and when the game is won or lost.
+///
+/// Rule files only need to contain what differs from the default. A building listed in
+/// `buildings` replaces the default rule for that building, the others keep theirs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RulesetFile")]
+pub struct Ruleset {
+    /// Stored in saves and replays, so they can only be loaded with the same rules.
+    pub id: String,
+    /// One rule per building except `Empty`, in the order of `Building::iter()`.
+    buildings: Vec<BuildingRule>,
+    pub terraform_cost: Resources,
+    pub infrastructure_cost: Resources,
+    /// Landscapes that can be turned into plains.
+    pub terraformable: Vec<Landscape>,
+    /// Every season that ends with at least this much instant CO2 advances the doom timer.
+    pub co2_threshold: i16,
+    /// The game is lost once the doom timer reaches this.
+    pub doom_timer_limit: u8,
+    /// Tech/economy, sustainability and education/culture all need to reach this to win.
+    pub win_target: i16,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildingRule {
+    pub building: Building,
+    pub cost: Resources,
+    /// Replaces `cost` on these landscapes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub landscape_costs: Vec<LandscapeCost>,
+    pub landscapes: Vec<Landscape>,
+    /// Education/culture needed before the building can be built.
+    #[serde(default)]
+    pub science: i16,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LandscapeCost {
+    pub landscape: Landscape,
+    pub cost: Resources,
+}
+
+impl Ruleset {
+    /// The default rules, shared so states don't each carry their own copy.
+    pub fn shared_default() -> Arc<Ruleset> {
+        DEFAULT_RULESET.clone()
+    }
+
+    pub fn rule(&self, building: Building) -> Option<&BuildingRule> {
+        self.buildings.get(building as usize)
+    }
+
+    pub fn buildings(&self) -> &[BuildingRule] {
+        &self.buildings
+    }
+
+    pub fn cost(&self, building: Building, tile: &Tile) -> Resources {
+        let Some(rule) = self.rule(building) else {
+            return Resources::new(0, 0, 0, 0, 0);
+        };
+        rule.landscape_costs
+            .iter()
+            .find(|c| c.landscape == tile.landscape)
+            .map_or(rule.cost, |c| c.cost)
+    }
+
+    /// Whether the building fits into the free spaces and is allowed on the landscape.
+    pub fn can_build(&self, building: Building, tile: &Tile) -> bool {
+        tile.has_room_for(building)
+            && self
+                .rule(building)
+                .is_some_and(|rule| rule.landscapes.contains(&tile.landscape))
+    }
+
+    pub fn has_enough_science(&self, building: Building, science: i16) -> bool {
+        self.rule(building).is_some_and(|rule| science >= rule.science)
+    }
+
+    pub fn can_terraform(&self, landscape: Landscape) -> bool {
+        self.terraformable.contains(&landscape)
+    }
+
+    pub fn is_won(&self, resources: &Resources) -> bool {
+        resources.sustainability >= self.win_target
+            && resources.education_culture >= self.win_target
+            && resources.tech_economy >= self.win_target
+    }
+
+    /// Loads a `.toml` file as TOML and anything else as JSON.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Ruleset> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|extension| extension == "toml") {
+            Ruleset::from_toml(&text)
+        } else {
+            Ok(Ruleset::from_json(&text)?)
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Ruleset> {
+        serde_json::from_str(json)
+    }
+
+    pub fn from_toml(text: &str) -> io::Result<Ruleset> {
+        toml::from_str(text).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+/// Only the id, rulesets with the same id are expected to be the same.
+impl Hash for Ruleset {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl Default for Ruleset {
+    fn default() -> Ruleset {
+        let rule = |building: Building, cost: Resources, landscapes: &[Landscape]| BuildingRule {
+            building,
+            cost,
+            landscape_costs: Vec::new(),
+            landscapes: landscapes.to_vec(),
+            science: 0,
+        };
+        let on_landscape = |mut rule: BuildingRule, landscape: Landscape, cost: Resources| {
+            rule.landscape_costs.push(LandscapeCost { landscape, cost });
+            rule
+        };
+        let with_science = |mut rule: BuildingRule, science: i16| {
+            rule.science = science;
+            rule
+        };
+
+        let buildings = vec![
+            on_landscape(
+                rule(Factory, Resources::new(3, 4, -2, 0, 1), &[Plains]),
+                Mountain,
+                Resources::new(3, 7, -2, 0, 1),
+            ),
+            rule(Store, Resources::new(1, 2, -1, 0, 0), &[Plains]),
+            rule(CoalPowerPlant, Resources::new(6, 5, -4, 0, 2), &[Plains, Mountain]),
+            on_landscape(
+                rule(Trees, Resources::new(0, -2, 1, 0, -2), &[Plains, Mountain, Forest]),
+                Forest,
+                Resources::new(0, -2, 2, 0, -2),
+            ),
+            on_landscape(
+                rule(River, Resources::new(0, -2, 2, 0, -1), &[Plains, Mountain, Forest]),
+                Mountain,
+                Resources::new(0, -2, 3, 0, -1),
+            ),
+            rule(Livestock, Resources::new(4, 1, 2, 0, 0), &[Plains, Mountain]),
+            rule(Field, Resources::new(0, -1, 2, 0, 0), &[Plains]),
+            with_science(rule(SolarPark, Resources::new(4, 1, 2, 0, -2), &[Plains, Desert]), 10),
+            with_science(rule(OffshoreTurbines, Resources::new(6, 1, 4, 0, -3), &[Ocean]), 15),
+            rule(Biotope, Resources::new(-3, 0, 2, 0, 0), &[Plains]),
+            rule(NationalPark, Resources::new(-6, 0, 4, 0, 0), &[Plains]),
+            rule(
+                EnvironmentalProtectionArea,
+                Resources::new(-9, 0, 6, 0, 0),
+                &[Plains, Swamp, Desert],
+            ),
+            rule(School, Resources::new(5, 0, -1, 5, 0), &[Plains]),
+            rule(Museum, Resources::new(3, 0, 0, 4, 0), &[Plains]),
+            on_landscape(
+                rule(Zoo, Resources::new(4, 0, 3, 2, 0), &[Plains, Forest]),
+                Mountain,
+                Resources::new(4, 0, 4, 2, 0),
+            ),
+            rule(Library, Resources::new(3, 1, 0, 3, 0), &[Plains]),
+            with_science(rule(University, Resources::new(6, 3, 0, 4, 0), &[Plains]), 10),
+        ];
+
+        Ruleset {
+            id: DEFAULT_RULESET_ID.to_string(),
+            buildings,
+            terraform_cost: Resources::new(3, 0, -3, 0, 0),
+            infrastructure_cost: Resources::new(2, 0, -3, 0, 0),
+            terraformable: vec![Mountain, Swamp, Desert, Forest],
+            co2_threshold: 20,
+            doom_timer_limit: 4,
+            win_target: 15,
+        }
+    }
+}
+
+/// What a rule file may contain, everything but the id is optional.
+#[derive(Deserialize)]
+#[serde(default)]
+struct RulesetFile {
+    /// Required, otherwise games played under different rules would share the default id.
+    id: Option<String>,
+    buildings: Vec<BuildingRule>,
+    terraform_cost: Resources,
+    infrastructure_cost: Resources,
+    terraformable: Vec<Landscape>,
+    co2_threshold: i16,
+    doom_timer_limit: u8,
+    win_target: i16,
+}
+
+impl Default for RulesetFile {
+    fn default() -> RulesetFile {
+        let defaults = Ruleset::default();
+        RulesetFile {
+            id: None,
+            buildings: Vec::new(),
+            terraform_cost: defaults.terraform_cost,
+            infrastructure_cost: defaults.infrastructure_cost,
+            terraformable: defaults.terraformable,
+            co2_threshold: defaults.co2_threshold,
+            doom_timer_limit: defaults.doom_timer_limit,
+            win_target: defaults.win_target,
+        }
+    }
+}
+
+impl TryFrom<RulesetFile> for Ruleset {
+    type Error = String;
+
+    fn try_from(file: RulesetFile) -> Result<Ruleset, String> {
+        let id = file.id.ok_or("rule files need an id")?;
+        let mut buildings = Ruleset::default().buildings;
+        for rule in file.buildings {
+            if rule.building == Empty {
+                return Err("Empty is not a building that can have rules".to_string());
+            }
+            let index = rule.building as usize;
+            buildings[index] = rule;
+        }
+        debug_assert!(Building::iter()
+            .filter(|&b| b != Empty)
+            .zip(&buildings)
+            .all(|(building, rule)| rule.building == building));
+        let rules = Ruleset {
+            id,
+            buildings,
+            terraform_cost: file.terraform_cost,
+            infrastructure_cost: file.infrastructure_cost,
+            terraformable: file.terraformable,
+            co2_threshold: file.co2_threshold,
+            doom_timer_limit: file.doom_timer_limit,
+            win_target: file.win_target,
+        };
+        if rules.id == DEFAULT_RULESET_ID && rules != Ruleset::default() {
+            return Err(format!("rules that differ from the default need an id other than {DEFAULT_RULESET_ID}"));
+        }
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_rules_match_the_board_game() {
+        let rules = Ruleset::default();
+        let mountain = Tile::empty(Mountain);
+        let forest = Tile::empty(Forest);
+        assert_eq!(rules.cost(Factory, &mountain), Resources::new(3, 7, -2, 0, 1));
+        assert_eq!(rules.cost(Trees, &forest), Resources::new(0, -2, 2, 0, -2));
+        assert_eq!(rules.cost(Trees, &mountain), Resources::new(0, -2, 1, 0, -2));
+        assert!(rules.can_build(CoalPowerPlant, &mountain));
+        assert!(!rules.can_build(Factory, &mountain));
+        assert!(!rules.has_enough_science(OffshoreTurbines, 14));
+        assert!(rules.has_enough_science(OffshoreTurbines, 15));
+        assert!(!rules.can_terraform(Plains));
+        assert!(!rules.can_build(Empty, &Tile::empty(Plains)));
+    }
+
+    #[test]
+    fn rule_files_only_override_what_they_contain() {
+        let json = r#"{"id": "cheap-solar", "win_target": 12,
+            "buildings": [{"building": "SolarPark", "landscapes": ["Desert"],
+                "cost": {"instant_co2": 1, "tech_economy": 1, "sustainability": 2,
+                         "education_culture": 0, "yearly_co2": -2}}]}"#;
+        let rules = Ruleset::from_json(json).unwrap();
+        assert_eq!(rules.id, "cheap-solar");
+        assert_eq!(rules.win_target, 12);
+        assert_eq!(rules.co2_threshold, 20);
+        assert!(rules.has_enough_science(SolarPark, 0));
+        assert!(!rules.can_build(SolarPark, &Tile::empty(Plains)));
+        assert_eq!(rules.rule(Store), Ruleset::default().rule(Store));
+
+        let toml = "id = \"short\"\ndoom_timer_limit = 2\nterraformable = [\"Swamp\"]\n";
+        let rules = Ruleset::from_toml(toml).unwrap();
+        assert_eq!(rules.doom_timer_limit, 2);
+        assert!(!rules.can_terraform(Mountain));
+        assert_eq!(rules.buildings(), Ruleset::default().buildings());
+    }
+
+    #[test]
+    fn default_rules_round_trip() {
+        let json = serde_json::to_string(&Ruleset::default()).unwrap();
+        assert_eq!(Ruleset::from_json(&json).unwrap(), Ruleset::default());
+        let toml = toml::to_string(&Ruleset::default()).unwrap();
+        assert_eq!(Ruleset::from_toml(&toml).unwrap(), Ruleset::default());
+    }
+
+    #[test]
+    fn changed_rules_need_their_own_id() {
+        assert!(Ruleset::from_json(r#"{"win_target": 12}"#).is_err());
+        assert!(Ruleset::from_json(r#"{"id": "default", "win_target": 12}"#).is_err());
+        assert_eq!(Ruleset::from_json(r#"{"id": "default"}"#).unwrap(), Ruleset::default());
+    }
+}
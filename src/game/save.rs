@@ -0,0 +1,146 @@
+use crate::game::game_state::{Action, GameState};
+use crate::game::ruleset::Ruleset;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::sync::Arc;
+
+pub const FORMAT_VERSION: u32 = 1;
+
+/// What ends up in a save file. The state is stored next to the history, so loading
+/// doesn't depend on the map generator or the rules staying the same. The initial
+/// state is optional, without it the history starts from the board dealt by `seed`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedGame {
+    pub format_version: u32,
+    pub seed: u64,
+    pub ruleset_id: String,
+    pub actions: Vec<Action>,
+    pub state: GameState,
+    #[serde(default)]
+    pub initial_state: Option<GameState>,
+}
+
+impl SavedGame {
+    pub fn new(state: &GameState, actions: &[Action]) -> SavedGame {
+        SavedGame {
+            format_version: FORMAT_VERSION,
+            seed: state.seed,
+            ruleset_id: state.rules().id.clone(),
+            actions: actions.to_vec(),
+            state: state.clone(),
+            initial_state: None,
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<SavedGame> {
+        SavedGame::load_with_rules(path, Ruleset::shared_default())
+    }
+
+    /// Loads a game that was played with `rules`, fails if it was saved with a different ruleset.
+    pub fn load_with_rules(path: impl AsRef<Path>, rules: Arc<Ruleset>) -> io::Result<SavedGame> {
+        let mut saved: SavedGame = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if saved.format_version > FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "save format {} is newer than the supported format {}",
+                    saved.format_version, FORMAT_VERSION
+                ),
+            ));
+        }
+        check_ruleset(&saved.ruleset_id, &rules)?;
+        for state in std::iter::once(&mut saved.state).chain(saved.initial_state.as_mut()) {
+            if state.tiles.len() != state.layout().len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} tiles don't fit the layout {}", state.tiles.len(), state.layout().name),
+                ));
+            }
+            state.set_rules(rules.clone());
+        }
+        Ok(saved)
+    }
+}
+
+pub(crate) fn check_ruleset(ruleset_id: &str, rules: &Ruleset) -> io::Result<()> {
+    if ruleset_id == rules.id {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("saved with ruleset {ruleset_id}, but the rules are {}", rules.id),
+    ))
+}
+
+impl GameState {
+    /// Saves the state together with the actions that led to it.
+    pub fn save(&self, path: impl AsRef<Path>, history: &[Action]) -> io::Result<()> {
+        SavedGame::new(self, history).save(path)
+    }
+
+    /// Loads only the state, use `SavedGame::load` to also get the history.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<GameState> {
+        Ok(SavedGame::load(path)?.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut state = GameState::initialize_with_seed(3);
+        let history: Vec<Action> = state.legal_actions.iter().take(1).copied().collect();
+        history.iter().for_each(|&action| state.advance(action));
+
+        let path = std::env::temp_dir().join(format!("terra2_save_round_trip_{}.json", std::process::id()));
+        state.save(&path, &history).unwrap();
+        let saved = SavedGame::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(saved, SavedGame::new(&state, &history));
+        assert_eq!(saved.state.key(), state.key());
+    }
+
+    #[test]
+    fn newer_format_is_rejected() {
+        let mut saved = SavedGame::new(&GameState::initialize_with_seed(3), &[]);
+        saved.format_version = FORMAT_VERSION + 1;
+
+        let path = std::env::temp_dir().join(format!("terra2_save_newer_format_{}.json", std::process::id()));
+        saved.save(&path).unwrap();
+        let error = SavedGame::load(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn saves_only_load_with_their_ruleset() {
+        let mut rules = Ruleset::default();
+        rules.id = "variant".to_string();
+        rules.win_target = 10;
+        let rules = Arc::new(rules);
+        let state = GameState::initialize_with_rules(3, rules.clone());
+
+        let path = std::env::temp_dir().join(format!("terra2_save_ruleset_{}.json", std::process::id()));
+        state.save(&path, &[]).unwrap();
+        let default_error = SavedGame::load(&path).unwrap_err();
+        let saved = SavedGame::load_with_rules(&path, rules).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(default_error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(saved.ruleset_id, "variant");
+        assert_eq!(saved.state, state);
+    }
+}
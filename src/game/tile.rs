@@ -1,74 +1,76 @@
 use crate::game::buildings::Building;
-use crate::game::buildings::Building::{Empty, EnvironmentalProtectionArea, NationalPark};
+use crate::game::buildings::Building::Empty;
 use crate::game::game_state::Action;
 use crate::game::game_state::Action::BuildInfrastructure;
 use crate::game::tile::Landscape::Plains;
-use lazy_static::lazy_static;
-use serde::{Deserialize, Serialize};
-
-lazy_static! {
-    pub static ref POSSIBLE_CONNECTIONS: Vec<Vec<Action>> = vec![
-        vec![BuildInfrastructure(0, 2)],
-        vec![BuildInfrastructure(1, 4)],
-        vec![
-            BuildInfrastructure(2, 0),
-            BuildInfrastructure(2, 4),
-            BuildInfrastructure(2, 5),
-            BuildInfrastructure(2, 6),
-        ],
-        vec![BuildInfrastructure(3, 5)],
-        vec![
-            BuildInfrastructure(4, 1),
-            BuildInfrastructure(4, 2),
-            BuildInfrastructure(4, 6),
-            BuildInfrastructure(4, 7),
-        ],
-        vec![
-            BuildInfrastructure(5, 2),
-            BuildInfrastructure(5, 3),
-            BuildInfrastructure(5, 6),
-            BuildInfrastructure(5, 8),
-        ],
-        vec![
-            BuildInfrastructure(6, 2),
-            BuildInfrastructure(6, 4),
-            BuildInfrastructure(6, 5),
-            BuildInfrastructure(6, 7),
-            BuildInfrastructure(6, 8),
-            BuildInfrastructure(6, 10),
-        ],
-        vec![
-            BuildInfrastructure(7, 4),
-            BuildInfrastructure(7, 6),
-            BuildInfrastructure(7, 9),
-            BuildInfrastructure(7, 10),
-        ],
-        vec![
-            BuildInfrastructure(8, 4),
-            BuildInfrastructure(8, 6),
-            BuildInfrastructure(8, 10),
-            BuildInfrastructure(8, 11),
-        ],
-        vec![BuildInfrastructure(9, 7)],
-        vec![
-            BuildInfrastructure(10, 6),
-            BuildInfrastructure(10, 7),
-            BuildInfrastructure(10, 8),
-            BuildInfrastructure(10, 12),
-        ],
-        vec![BuildInfrastructure(11, 8)],
-        vec![BuildInfrastructure(12, 10)],
-    ];
-}
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use strum_macros::EnumString;
+
+/// Largest map a layout may describe, bounded by the bits in `Connections`.
+pub const MAX_TILES: usize = 128;
+
 #[derive(Copy, Clone, Hash, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub struct Tile {
     pub spaces: [Building; 3],
-    pub connections: [bool; 13],
+    pub connections: Connections,
     pub landscape: Landscape,
     pub spaces_left: u8,
     pub usable: bool,
 }
-#[derive(Eq, Hash, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+
+/// The tiles a tile has roads to. A bit set keeps `Tile` `Copy` on every map size,
+/// it is saved as a list of flags like the fixed-size array it replaced.
+#[derive(Copy, Clone, Hash, Eq, PartialEq, Debug, Default)]
+pub struct Connections(u128);
+
+impl Connections {
+    pub fn contains(self, tile: usize) -> bool {
+        tile < MAX_TILES && self.0 & (1 << tile) != 0
+    }
+
+    pub fn insert(&mut self, tile: usize) {
+        self.0 |= 1 << tile;
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = usize> {
+        (0..MAX_TILES).filter(move |&tile| self.contains(tile))
+    }
+}
+
+impl Serialize for Connections {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let length = MAX_TILES - self.0.leading_zeros() as usize;
+        let flags: Vec<bool> = (0..length).map(|tile| self.contains(tile)).collect();
+        flags.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Connections {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let flags = Vec::<bool>::deserialize(deserializer)?;
+        if flags.len() > MAX_TILES {
+            return Err(serde::de::Error::invalid_length(flags.len(), &"at most 128 tiles"));
+        }
+        let mut connections = Connections::default();
+        flags
+            .iter()
+            .enumerate()
+            .filter(|(_, &connected)| connected)
+            .for_each(|(tile, _)| connections.insert(tile));
+        Ok(connections)
+    }
+}
+/// A broken invariant found by `Tile::validate`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InvalidTile {
+    SpacesLeftMismatch { spaces_left: u8, empty: u8 },
+    BuiltOnUnusableTile,
+    /// A building fills more or fewer spaces than it should, e.g. a duplicate or half a NationalPark.
+    WrongSpaceCount { building: Building, occupied: u8 },
+}
+
+#[derive(Eq, Hash, PartialEq, Copy, Clone, Debug, EnumString, Serialize, Deserialize)]
+#[strum(ascii_case_insensitive)]
 #[repr(u8)]
 pub enum Landscape {
     Plains,
@@ -86,43 +88,150 @@ impl Tile {
             spaces: [Empty; 3],
             spaces_left: 3,
             usable: false,
-            connections: [false; 13],
+            connections: Connections::default(),
         }
     }
 
     pub fn connect(&mut self, tile_to: usize) {
-        self.connections[tile_to] = true;
+        self.connections.insert(tile_to);
     }
 
+    /// Puts the building into the first free spaces, multi-space buildings take as
+    /// many as `Building::slots` says. Callers have to check `Ruleset::can_build` first.
     pub fn build(&mut self, building: Building) {
-        if building == EnvironmentalProtectionArea {
-            self.spaces[0] = building;
-            self.spaces[1] = building;
-            self.spaces[2] = building;
-            self.spaces_left -= 3;
-        } else if building == NationalPark {
-            self.spaces[0] = building;
-            self.spaces[1] = building;
-            self.spaces_left -= 2;
-        } else {
-            if self.spaces_left == 3 {
-                self.spaces[0] = building;
-            } else if self.spaces_left == 2 {
-                self.spaces[1] = building;
-            } else if self.spaces_left == 1 {
-                self.spaces[2] = building;
+        let mut slots_needed = building.slots();
+        for space in self.spaces.iter_mut().filter(|s| **s == Empty) {
+            if slots_needed == 0 {
+                break;
+            }
+            *space = building;
+            slots_needed -= 1;
+        }
+        debug_assert_eq!(slots_needed, 0, "not enough free spaces for {building:?}");
+        self.spaces_left -= building.slots() - slots_needed;
+    }
+
+    /// Enough free spaces and not built here yet, whether the landscape allows it is up to the ruleset.
+    pub fn has_room_for(&self, building: Building) -> bool {
+        building != Empty && !self.spaces.contains(&building) && self.spaces_left >= building.slots()
+    }
+
+    pub fn validate(&self) -> Result<(), InvalidTile> {
+        let empty = self.spaces.iter().filter(|&&s| s == Empty).count() as u8;
+        if self.spaces_left != empty {
+            return Err(InvalidTile::SpacesLeftMismatch {
+                spaces_left: self.spaces_left,
+                empty,
+            });
+        }
+        if !self.usable && empty != 3 {
+            return Err(InvalidTile::BuiltOnUnusableTile);
+        }
+        for &building in self.spaces.iter().filter(|&&s| s != Empty) {
+            let occupied = self.spaces.iter().filter(|&&s| s == building).count() as u8;
+            if occupied != building.slots() {
+                return Err(InvalidTile::WrongSpaceCount { building, occupied });
             }
-            self.spaces_left -= 1;
         }
+        Ok(())
     }
 
     pub fn terraform(&mut self) {
         self.landscape = Plains;
     }
 }
-pub fn filter_actual_connections(tiles: &[Tile; 13], possible_connection: Action) -> bool {
+pub fn filter_actual_connections(tiles: &[Tile], possible_connection: Action) -> bool {
     match possible_connection {
-        BuildInfrastructure(from, to) => !tiles[from].connections[to] && !tiles[to].usable,
+        BuildInfrastructure(from, to) => !tiles[from].connections.contains(to) && !tiles[to].usable,
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::buildings::Building::{EnvironmentalProtectionArea, Factory, NationalPark, Store};
+    use crate::game::game_state::Action::Build;
+    use crate::game::game_state::GameState;
+    use rand::prelude::{IndexedRandom, StdRng};
+    use rand::SeedableRng;
+
+    #[test]
+    fn national_park_goes_into_free_spaces() {
+        let mut tile = Tile::empty(Plains);
+        tile.usable = true;
+        tile.build(Factory);
+        tile.build(NationalPark);
+        assert_eq!(tile.spaces, [Factory, NationalPark, NationalPark]);
+        assert_eq!(tile.spaces_left, 0);
+        assert_eq!(tile.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_catches_broken_bookkeeping() {
+        let mut tile = Tile::empty(Plains);
+        tile.usable = true;
+        tile.build(Store);
+        tile.spaces_left = 3;
+        assert_eq!(
+            tile.validate(),
+            Err(InvalidTile::SpacesLeftMismatch {
+                spaces_left: 3,
+                empty: 2
+            })
+        );
+
+        tile.spaces = [EnvironmentalProtectionArea, EnvironmentalProtectionArea, Empty];
+        tile.spaces_left = 1;
+        assert_eq!(
+            tile.validate(),
+            Err(InvalidTile::WrongSpaceCount {
+                building: EnvironmentalProtectionArea,
+                occupied: 2
+            })
+        );
+    }
+
+    #[test]
+    fn connections_read_the_old_fixed_size_arrays() {
+        let mut flags = [false; 13];
+        flags[2] = true;
+        flags[12] = true;
+        let connections: Connections = serde_json::from_str(&serde_json::to_string(&flags).unwrap()).unwrap();
+        assert_eq!(connections.iter().collect::<Vec<_>>(), vec![2, 12]);
+
+        let mut far = Connections::default();
+        far.insert(127);
+        let json = serde_json::to_string(&far).unwrap();
+        assert_eq!(serde_json::from_str::<Connections>(&json).unwrap(), far);
+        assert!(serde_json::from_str::<Connections>(&serde_json::to_string(&vec![true; 129]).unwrap()).is_err());
+    }
+
+    /// Plays random games and checks after every move that the tiles are consistent
+    /// and that every building that was built is still there.
+    #[test]
+    fn random_games_keep_slot_bookkeeping_intact() {
+        for seed in 0..200 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut state = GameState::initialize_with_seed(seed);
+            let mut built: Vec<Vec<Building>> = vec![Vec::new(); state.tiles.len()];
+
+            for _ in 0..60 {
+                let Some(&action) = state.legal_actions.choose(&mut rng) else {
+                    break;
+                };
+                if let Build(building, tile) = action {
+                    built[tile].push(building);
+                }
+                state.advance(action);
+
+                for (index, tile) in state.tiles.iter().enumerate() {
+                    assert_eq!(tile.validate(), Ok(()), "seed {seed}, tile {index}: {tile:?}");
+                    for building in &built[index] {
+                        assert!(tile.spaces.contains(building), "seed {seed}: lost {building:?} on {index}");
+                    }
+                }
+            }
+        }
+    }
+}
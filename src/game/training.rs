@@ -0,0 +1,307 @@
+use crate::game::features::StateFeatures;
+use crate::game::game_state::{Action, GameState, Status};
+use crate::game::map_layout::MapLayout;
+use crate::game::reinforcement_ai::{EpisodeEnd, EpsilonGreedy, FeatureAgent, RewardShaping};
+use crate::game::ruleset::Ruleset;
+use crate::game::save::{check_ruleset, FORMAT_VERSION};
+use rurel::mdp::State;
+use rurel::strategy::learn::QLearning;
+use rurel::AgentTrainer;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+use std::sync::Arc;
+
+pub const DEFAULT_CHECKPOINT_PATH: &str = "checkpoint.json";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TrainingConfig {
+    /// Total episodes, including the ones a resumed checkpoint already played.
+    pub episodes: u64,
+    /// Episodes between two reports and checkpoints.
+    pub epoch_size: u64,
+    /// Learning rate.
+    pub alpha: f64,
+    /// Discount of future rewards.
+    pub gamma: f64,
+    /// Chance of a random action instead of the best known one.
+    pub epsilon: f64,
+    /// Value of actions that were never tried.
+    pub initial_value: f64,
+    pub max_steps: u32,
+    pub shaping: RewardShaping,
+    /// Episode `n` is played on the board dealt by `seed + n`.
+    pub seed: u64,
+}
+
+impl Default for TrainingConfig {
+    fn default() -> TrainingConfig {
+        TrainingConfig {
+            episodes: 1000,
+            epoch_size: 100,
+            alpha: 0.2,
+            gamma: 0.9,
+            epsilon: 0.2,
+            initial_value: 0.0,
+            max_steps: 100,
+            shaping: RewardShaping::default(),
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EpochReport {
+    pub epoch: u64,
+    pub episodes: u64,
+    pub wins: u64,
+    /// States in the Q-table after the epoch.
+    pub states: usize,
+}
+
+impl EpochReport {
+    pub fn win_rate(&self) -> f64 {
+        self.wins as f64 / self.episodes.max(1) as f64
+    }
+}
+
+/// The learned values in a form JSON can hold, the Q-table itself is keyed by states.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint<S> {
+    pub format_version: u32,
+    pub ruleset_id: String,
+    pub episodes: u64,
+    pub entries: Vec<QEntry<S>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct QEntry<S> {
+    pub state: S,
+    pub action: Action,
+    pub value: f64,
+}
+
+/// States whose ruleset isn't saved with them and has to be attached again after loading.
+pub trait AttachRules {
+    fn attach_rules(&mut self, rules: &Arc<Ruleset>);
+}
+
+/// The rules are part of a state's hash, without them no loaded state would match a trained one.
+impl AttachRules for GameState {
+    fn attach_rules(&mut self, rules: &Arc<Ruleset>) {
+        self.set_rules(rules.clone());
+    }
+}
+
+/// Features only hold what the rules led to, not the rules themselves.
+impl AttachRules for StateFeatures {
+    fn attach_rules(&mut self, _: &Arc<Ruleset>) {}
+}
+
+impl<S: State<A = Action> + AttachRules + Serialize + DeserializeOwned> Checkpoint<S> {
+    pub fn new(values: &HashMap<S, HashMap<Action, f64>>, episodes: u64, rules: &Ruleset) -> Checkpoint<S> {
+        let entries = values
+            .iter()
+            .flat_map(|(state, actions)| {
+                actions.iter().map(|(&action, &value)| QEntry {
+                    state: state.clone(),
+                    action,
+                    value,
+                })
+            })
+            .collect();
+        Checkpoint {
+            format_version: FORMAT_VERSION,
+            ruleset_id: rules.id.clone(),
+            episodes,
+            entries,
+        }
+    }
+
+    pub fn values(self) -> HashMap<S, HashMap<Action, f64>> {
+        let mut values: HashMap<S, HashMap<Action, f64>> = HashMap::new();
+        for entry in self.entries {
+            values.entry(entry.state).or_default().insert(entry.action, entry.value);
+        }
+        values
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Loads a checkpoint that was trained with `rules` and gives its states these rules.
+    pub fn load(path: impl AsRef<Path>, rules: &Arc<Ruleset>) -> io::Result<Checkpoint<S>> {
+        let mut checkpoint: Checkpoint<S> = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if checkpoint.format_version > FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "checkpoint format {} is newer than the supported format {}",
+                    checkpoint.format_version, FORMAT_VERSION
+                ),
+            ));
+        }
+        check_ruleset(&checkpoint.ruleset_id, rules)?;
+        for entry in &mut checkpoint.entries {
+            entry.state.attach_rules(rules);
+        }
+        Ok(checkpoint)
+    }
+}
+
+/// Tabular Q-learning with rurel over whole games, one episode per board.
+/// The values are keyed by `StateFeatures`, so they carry over between similar states.
+pub struct Training {
+    pub trainer: AgentTrainer<StateFeatures>,
+    pub config: TrainingConfig,
+    rules: Arc<Ruleset>,
+    layout: Arc<MapLayout>,
+    /// Episodes played so far, also the ones before a resume.
+    episodes: u64,
+}
+
+impl Training {
+    pub fn new(config: TrainingConfig, rules: Arc<Ruleset>, layout: Arc<MapLayout>) -> Training {
+        Training {
+            trainer: AgentTrainer::new(),
+            config,
+            rules,
+            layout,
+            episodes: 0,
+        }
+    }
+
+    /// Continues from the values and episode count of `checkpoint`.
+    pub fn resume(&mut self, checkpoint: Checkpoint<StateFeatures>) {
+        self.episodes = checkpoint.episodes;
+        self.trainer.import_state(checkpoint.values());
+    }
+
+    pub fn episodes(&self) -> u64 {
+        self.episodes
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.episodes >= self.config.episodes
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint<StateFeatures> {
+        Checkpoint::new(self.trainer.learned_values(), self.episodes, &self.rules)
+    }
+
+    /// Plays up to `epoch_size` episodes. Greedy moves follow the values as they were
+    /// at the start of the epoch.
+    pub fn train_epoch(&mut self) -> EpochReport {
+        let learning = QLearning::new(self.config.alpha, self.config.gamma, self.config.initial_value);
+        let exploration = EpsilonGreedy {
+            epsilon: self.config.epsilon,
+            values: self.trainer.export_learned_values(),
+        };
+        let episodes = self.config.epoch_size.max(1).min(self.config.episodes.saturating_sub(self.episodes));
+        let mut wins = 0;
+        for _ in 0..episodes {
+            let seed = self.config.seed.wrapping_add(self.episodes);
+            let state = GameState::initialize_with_layout(seed, self.rules.clone(), self.layout.clone());
+            let mut agent = FeatureAgent::new(state, self.config.shaping);
+            if !agent.state.legal_actions.is_empty() {
+                let mut end = EpisodeEnd::new(self.config.max_steps);
+                self.trainer.train(&mut agent, &learning, &mut end, &exploration);
+            }
+            wins += (agent.state.status == Status::Win) as u64;
+            self.episodes += 1;
+        }
+        EpochReport {
+            epoch: self.episodes.div_ceil(self.config.epoch_size.max(1)),
+            episodes,
+            wins,
+            states: self.trainer.learned_values().len(),
+        }
+    }
+}
+
+/// Runs epochs until `training` has played all its episodes, `on_epoch` sees every report.
+pub fn train(training: &mut Training, mut on_epoch: impl FnMut(&Training, &EpochReport)) -> Vec<EpochReport> {
+    let mut reports = Vec::new();
+    while !training.is_done() {
+        let report = training.train_epoch();
+        on_epoch(training, &report);
+        reports.push(report);
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small_config() -> TrainingConfig {
+        TrainingConfig {
+            episodes: 6,
+            epoch_size: 4,
+            max_steps: 20,
+            seed: 7,
+            ..TrainingConfig::default()
+        }
+    }
+
+    #[test]
+    fn training_reports_every_epoch() {
+        let mut training = Training::new(small_config(), Ruleset::shared_default(), MapLayout::shared_classic());
+        let mut seen = 0;
+        let reports = train(&mut training, |_, _| seen += 1);
+        assert_eq!(seen, 2);
+        assert_eq!(reports.iter().map(|r| r.episodes).collect::<Vec<_>>(), vec![4, 2]);
+        assert_eq!(reports.iter().map(|r| r.epoch).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(reports.iter().all(|r| (0.0..=1.0).contains(&r.win_rate())));
+        assert!(reports[1].states > 0);
+        assert_eq!(training.episodes(), 6);
+    }
+
+    #[test]
+    fn checkpoints_round_trip_and_resume() {
+        let rules = Ruleset::shared_default();
+        let mut training = Training::new(small_config(), rules.clone(), MapLayout::shared_classic());
+        training.train_epoch();
+        let checkpoint = training.checkpoint();
+        assert_eq!(checkpoint.episodes, 4);
+
+        let path = std::env::temp_dir().join(format!("terra2_checkpoint_{}.json", std::process::id()));
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::<StateFeatures>::load(&path, &rules).unwrap();
+        let mut other_rules = Ruleset::default();
+        other_rules.id = "other".to_string();
+        assert!(Checkpoint::<StateFeatures>::load(&path, &Arc::new(other_rules)).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.entries.len(), checkpoint.entries.len());
+
+        let mut resumed = Training::new(small_config(), rules, MapLayout::shared_classic());
+        resumed.resume(loaded);
+        assert_eq!(resumed.trainer.learned_values(), training.trainer.learned_values());
+        assert_eq!(resumed.train_epoch().episodes, 2);
+        assert!(resumed.is_done());
+    }
+
+    #[test]
+    fn loaded_game_states_match_under_their_rules() {
+        let mut rules = Ruleset::default();
+        rules.id = "short".to_string();
+        rules.doom_timer_limit = 2;
+        let rules = Arc::new(rules);
+        let state = GameState::initialize_with_rules(3, rules.clone());
+        let action = state.legal_actions[0];
+        let values = HashMap::from([(state.clone(), HashMap::from([(action, 1.5)]))]);
+
+        let path = std::env::temp_dir().join(format!("terra2_game_checkpoint_{}.json", std::process::id()));
+        Checkpoint::new(&values, 1, &rules).save(&path).unwrap();
+        let loaded = Checkpoint::<GameState>::load(&path, &rules).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.values(), values);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::game::game_state::Action;
+
+pub const DEFAULT_CAPACITY: usize = 1 << 18;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub key: u64,
+    pub depth: u16,
+    pub eval: i16,
+    pub best_action: Option<Action>,
+}
+
+/// Fixed size, always-allocated cache of search results keyed by a position hash.
+/// Each key maps to exactly one slot, on collision the deeper search wins.
+pub struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+}
+
+impl TranspositionTable {
+    pub fn new(capacity: usize) -> TranspositionTable {
+        TranspositionTable {
+            entries: vec![None; capacity.max(1)],
+        }
+    }
+
+    pub fn get(&self, key: u64) -> Option<&Entry> {
+        self.entries[self.index(key)].as_ref().filter(|e| e.key == key)
+    }
+
+    pub fn insert(&mut self, entry: Entry) {
+        let index = self.index(entry.key);
+        match self.entries[index] {
+            Some(existing) if existing.key != entry.key && existing.depth > entry.depth => {}
+            _ => self.entries[index] = Some(entry),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|e| *e = None);
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key % self.entries.len() as u64) as usize
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> TranspositionTable {
+        TranspositionTable::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::game_state::Action::Terraform;
+
+    fn entry(key: u64, depth: u16) -> Entry {
+        Entry {
+            key,
+            depth,
+            eval: depth as i16,
+            best_action: Some(Terraform(0)),
+        }
+    }
+
+    #[test]
+    fn get_only_returns_matching_key() {
+        let mut table = TranspositionTable::new(4);
+        table.insert(entry(1, 2));
+        assert_eq!(table.get(1), Some(&entry(1, 2)));
+        assert_eq!(table.get(5), None);
+    }
+
+    #[test]
+    fn collision_keeps_deeper_entry() {
+        let mut table = TranspositionTable::new(4);
+        table.insert(entry(1, 3));
+        table.insert(entry(5, 1));
+        assert_eq!(table.get(1), Some(&entry(1, 3)));
+        table.insert(entry(5, 4));
+        assert_eq!(table.get(5), Some(&entry(5, 4)));
+        assert_eq!(table.get(1), None);
+    }
+}
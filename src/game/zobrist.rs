@@ -0,0 +1,100 @@
+use crate::game::buildings::Building;
+use crate::game::game_state::{Season, Status};
+use crate::game::resources::Resources;
+use crate::game::tile::{Tile, MAX_TILES};
+use lazy_static::lazy_static;
+use strum::EnumCount;
+
+const SLOTS: usize = 3;
+const LANDSCAPES: usize = 6;
+const SEASONS: usize = 4;
+const STATUSES: usize = 3;
+const DOOM_TIMER_VALUES: usize = 8;
+const RESOURCE_SEED: u64 = 0x5EED_2E50;
+
+pub struct ZobristKeys {
+    buildings: [[[u64; Building::COUNT]; SLOTS]; MAX_TILES],
+    landscapes: [[u64; LANDSCAPES]; MAX_TILES],
+    usable: [u64; MAX_TILES],
+    seasons: [u64; SEASONS],
+    statuses: [u64; STATUSES],
+    doom_timer: [u64; DOOM_TIMER_VALUES],
+}
+
+lazy_static! {
+    pub static ref ZOBRIST: ZobristKeys = ZobristKeys::generate();
+}
+
+impl ZobristKeys {
+    fn generate() -> ZobristKeys {
+        let mut seed = 0x7E22_A200_u64;
+        let mut next = || splitmix64(&mut seed);
+
+        let mut keys = ZobristKeys {
+            buildings: [[[0; Building::COUNT]; SLOTS]; MAX_TILES],
+            landscapes: [[0; LANDSCAPES]; MAX_TILES],
+            usable: [0; MAX_TILES],
+            seasons: [0; SEASONS],
+            statuses: [0; STATUSES],
+            doom_timer: [0; DOOM_TIMER_VALUES],
+        };
+        keys.buildings.iter_mut().flatten().flatten().for_each(|k| *k = next());
+        keys.landscapes.iter_mut().flatten().for_each(|k| *k = next());
+        keys.usable.iter_mut().for_each(|k| *k = next());
+        keys.seasons.iter_mut().for_each(|k| *k = next());
+        keys.statuses.iter_mut().for_each(|k| *k = next());
+        keys.doom_timer.iter_mut().for_each(|k| *k = next());
+        keys
+    }
+
+    /// Everything a single tile contributes: its slots, landscape and usable flag.
+    /// Connections are left out, they only ever lead to tiles that are already usable.
+    pub fn tile(&self, index: usize, tile: &Tile) -> u64 {
+        let mut key = self.landscapes[index][tile.landscape as usize];
+        for (slot, &building) in tile.spaces.iter().enumerate() {
+            key ^= self.buildings[index][slot][building as usize];
+        }
+        if tile.usable {
+            key ^= self.usable[index];
+        }
+        key
+    }
+
+    pub fn season(&self, season: Season) -> u64 {
+        self.seasons[season as usize]
+    }
+
+    pub fn status(&self, status: Status) -> u64 {
+        self.statuses[status as usize]
+    }
+
+    pub fn doom_timer(&self, doom_timer: u8) -> u64 {
+        self.doom_timer[doom_timer as usize % DOOM_TIMER_VALUES]
+    }
+
+    /// Resources are unbounded, so instead of a table every (resource, value) pair
+    /// is mixed on the fly.
+    pub fn resources(&self, resources: &Resources) -> u64 {
+        [
+            resources.instant_co2,
+            resources.tech_economy,
+            resources.sustainability,
+            resources.education_culture,
+            resources.yearly_co2,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |key, (index, &value)| {
+            let mut seed = RESOURCE_SEED ^ ((index as u64) << 16) ^ value as u16 as u64;
+            key ^ splitmix64(&mut seed)
+        })
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
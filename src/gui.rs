@@ -0,0 +1,613 @@
+use crate::game::buildings::Building;
+use crate::game::game_state::Action::{Build, BuildInfrastructure, Terraform};
+use crate::game::game_state::Status::{Loss, Running, Win};
+use crate::game::game_state::{Action, GameState, Season};
+use crate::game::history::Game;
+use crate::game::map_layout::MapLayout;
+use crate::game::tile::Landscape;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::{Point, Rect};
+use sdl2::render::{Canvas, RenderTarget};
+use sdl2::surface::Surface;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+pub const WIDTH: u32 = 960;
+pub const HEIGHT: u32 = 720;
+/// Largest tile radius, bigger maps are scaled down to fit into the map area.
+const TILE_RADIUS: f64 = 56.0;
+const MAP_CENTER: (f64, f64) = (560.0, 330.0);
+const MAP_AREA: (f64, f64) = (720.0, 600.0);
+const BUTTON_SIZE: u32 = 56;
+const BUTTON_Y: i32 = 630;
+const FONT_SCALE: u32 = 3;
+
+/// 3x5 pixel glyphs for the digits and the minus sign, one row per three bits.
+const DIGITS: [u16; 11] = [
+    0b111_101_101_101_111,
+    0b010_110_010_010_111,
+    0b111_001_111_100_111,
+    0b111_001_111_001_111,
+    0b101_101_111_001_001,
+    0b111_100_111_001_111,
+    0b111_100_111_101_111,
+    0b111_001_010_010_010,
+    0b111_101_111_101_111,
+    0b111_101_111_001_111,
+    0b000_000_111_000_000,
+];
+
+/// Interaction state that is not part of the game itself.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct View {
+    pub selected: Option<usize>,
+}
+
+/// Where the tiles of a layout go on screen.
+#[derive(Copy, Clone, Debug)]
+struct Board<'a> {
+    positions: &'a [(i32, i32)],
+    radius: f64,
+    center: (f64, f64),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Click {
+    Tile(usize),
+    Action(Action),
+    Nothing,
+}
+
+/// Opens a window and lets the player pick legal actions with the mouse.
+/// A click on a tile selects it, its actions show up as buttons at the bottom,
+/// a click on a highlighted neighbour connects it. U and R undo and redo.
+pub fn run(mut game: Game) -> Result<(), String> {
+    let sdl = sdl2::init()?;
+    let window = sdl
+        .video()?
+        .window("Terra2", WIDTH, HEIGHT)
+        .position_centered()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    let mut events = sdl.event_pump()?;
+    let mut view = View::default();
+
+    loop {
+        draw(&mut canvas, game.state(), view)?;
+        canvas.present();
+        let title = window_title(game.state());
+        canvas.window_mut().set_title(&title).map_err(|e| e.to_string())?;
+
+        match events.wait_event() {
+            Event::Quit { .. }
+            | Event::KeyDown {
+                keycode: Some(Keycode::Escape),
+                ..
+            } => return Ok(()),
+            Event::KeyDown {
+                keycode: Some(Keycode::U),
+                ..
+            } => {
+                game.undo();
+            }
+            Event::KeyDown {
+                keycode: Some(Keycode::R),
+                ..
+            } => {
+                game.redo();
+            }
+            Event::MouseButtonDown {
+                mouse_btn: MouseButton::Left,
+                x,
+                y,
+                ..
+            } => match hit_test(game.state(), view, x, y) {
+                Click::Tile(tile) => view.selected = Some(tile),
+                Click::Action(action) => {
+                    if let Err(error) = game.play(action) {
+                        println!("Can't do that: {error}");
+                    }
+                    if let BuildInfrastructure(_, to) = action {
+                        view.selected = Some(to);
+                    }
+                }
+                Click::Nothing => view.selected = None,
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Draws the board without a display into a software surface and writes it as PNG.
+pub fn render_png(state: &GameState, view: View, path: impl AsRef<Path>) -> Result<(), String> {
+    let surface = render_surface(state, view)?;
+    let rgb = surface.with_lock(|pixels| {
+        let pitch = surface.pitch() as usize;
+        (0..surface.height() as usize)
+            .flat_map(|row| {
+                pixels[row * pitch..row * pitch + 3 * surface.width() as usize]
+                    .iter()
+                    .copied()
+            })
+            .collect::<Vec<u8>>()
+    });
+    let mut file = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    write_png(&mut file, surface.width(), surface.height(), &rgb).map_err(|e| e.to_string())
+}
+
+/// Renders in RGB888 because SDL can't draw lines on 24 bit surfaces, the result is converted to RGB24.
+pub fn render_surface(state: &GameState, view: View) -> Result<Surface<'static>, String> {
+    let mut canvas = Surface::new(WIDTH, HEIGHT, PixelFormatEnum::RGB888)?.into_canvas()?;
+    draw(&mut canvas, state, view)?;
+    canvas.into_surface().convert_format(PixelFormatEnum::RGB24)
+}
+
+pub fn draw<T: RenderTarget>(canvas: &mut Canvas<T>, state: &GameState, view: View) -> Result<(), String> {
+    canvas.set_draw_color(match state.status {
+        Running => Color::RGB(24, 28, 36),
+        Win => Color::RGB(20, 60, 30),
+        Loss => Color::RGB(70, 20, 20),
+    });
+    canvas.clear();
+
+    let board = Board::fit(state.layout());
+    let radius = board.radius - 2.0;
+    for (index, tile) in state.tiles.iter().enumerate() {
+        let (x, y) = board.center(index);
+        let color = landscape_color(tile.landscape);
+        fill_hex(canvas, x, y, radius, if tile.usable { color } else { dim(color) })?;
+    }
+    draw_connections(canvas, state, board)?;
+    let targets = connectable_tiles(state, view);
+    for (index, tile) in state.tiles.iter().enumerate() {
+        let (x, y) = board.center(index);
+        if view.selected == Some(index) {
+            outline_hex(canvas, x, y, radius, Color::WHITE)?;
+        } else if targets.contains(&index) {
+            outline_hex(canvas, x, y, radius, Color::YELLOW)?;
+        }
+        let (label_x, label_y) = ((x - board.scaled(4.0)) as i32, (y - board.scaled(36.0)) as i32);
+        draw_number(canvas, index as i32, label_x, label_y, Color::BLACK, board.font_scale())?;
+        let size = board.scaled(20.0) as u32;
+        for (slot, &building) in tile.spaces.iter().enumerate() {
+            canvas.set_draw_color(building_color(building));
+            let left = x - board.scaled(33.0) + board.scaled(23.0) * slot as f64;
+            canvas.fill_rect(Rect::new(left as i32, y as i32, size, size))?;
+        }
+    }
+
+    draw_hud(canvas, state)?;
+    for (rect, action, index) in action_buttons(state, view) {
+        canvas.set_draw_color(match action {
+            Build(building, _) => building_color(building),
+            BuildInfrastructure(_, to) => landscape_color(state.tiles[to].landscape),
+            Terraform(_) => Color::RGB(120, 80, 40),
+        });
+        canvas.fill_rect(rect)?;
+        draw_number(canvas, index as i32, rect.x() + 4, rect.y() + 4, Color::WHITE, FONT_SCALE)?;
+    }
+    Ok(())
+}
+
+pub fn hit_test(state: &GameState, view: View, x: i32, y: i32) -> Click {
+    if let Some((_, action, _)) = action_buttons(state, view)
+        .into_iter()
+        .find(|(rect, _, _)| rect.contains_point((x, y)))
+    {
+        return Click::Action(action);
+    }
+    let board = Board::fit(state.layout());
+    let Some(tile) = (0..state.tiles.len()).find(|&tile| {
+        let (center_x, center_y) = board.center(tile);
+        (x as f64 - center_x).hypot(y as f64 - center_y) < board.radius * 0.85
+    }) else {
+        return Click::Nothing;
+    };
+    match (view.selected, connectable_tiles(state, view).contains(&tile)) {
+        (Some(from), true) => Click::Action(BuildInfrastructure(from, tile)),
+        _ => Click::Tile(tile),
+    }
+}
+
+/// Build and terraform actions on the selected tile, with their index in `legal_actions`.
+/// The buttons shrink when there are too many to fit next to each other.
+fn action_buttons(state: &GameState, view: View) -> Vec<(Rect, Action, usize)> {
+    let Some(selected) = view.selected else {
+        return Vec::new();
+    };
+    let actions: Vec<(usize, Action)> = state
+        .legal_actions
+        .iter()
+        .enumerate()
+        .filter(|(_, &action)| matches!(action, Build(_, tile) | Terraform(tile) if tile == selected))
+        .map(|(index, &action)| (index, action))
+        .collect();
+    let step = ((WIDTH - 40) / actions.len().max(1) as u32).min(BUTTON_SIZE + 8);
+    let size = step - 8;
+    actions
+        .into_iter()
+        .enumerate()
+        .map(|(button, (index, action))| {
+            let x = 20 + button as i32 * step as i32;
+            (Rect::new(x, BUTTON_Y, size, size), action, index)
+        })
+        .collect()
+}
+
+fn connectable_tiles(state: &GameState, view: View) -> Vec<usize> {
+    state
+        .legal_actions
+        .iter()
+        .filter_map(|&action| match action {
+            BuildInfrastructure(from, to) if Some(from) == view.selected => Some(to),
+            _ => None,
+        })
+        .collect()
+}
+
+fn draw_connections<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    state: &GameState,
+    board: Board<'_>,
+) -> Result<(), String> {
+    canvas.set_draw_color(Color::RGB(230, 230, 230));
+    let width = board.font_scale() as i32 - 1;
+    for (from, tile) in state.tiles.iter().enumerate() {
+        for to in tile.connections.iter().filter(|&to| to > from) {
+            let (from_x, from_y) = board.center(from);
+            let (to_x, to_y) = board.center(to);
+            for offset in -width..=width {
+                canvas.draw_line(
+                    Point::new(from_x as i32 + offset, from_y as i32),
+                    Point::new(to_x as i32 + offset, to_y as i32),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Resource bars on the left, the goal resources are marked at the win target and CO2 at the threshold.
+/// Below them the four seasons with the current one filled and the doom timer.
+fn draw_hud<T: RenderTarget>(canvas: &mut Canvas<T>, state: &GameState) -> Result<(), String> {
+    let resources = &state.resources;
+    let (target, threshold) = (state.rules().win_target, state.rules().co2_threshold);
+    let bars = [
+        (resources.tech_economy, target, Color::RGB(230, 140, 40)),
+        (resources.sustainability, target, Color::RGB(60, 180, 70)),
+        (resources.education_culture, target, Color::RGB(70, 120, 230)),
+        (resources.instant_co2, threshold, Color::RGB(200, 50, 50)),
+        (resources.yearly_co2, threshold, Color::RGB(140, 40, 40)),
+    ];
+    for (row, &(value, mark, color)) in bars.iter().enumerate() {
+        let y = 20 + row as i32 * 36;
+        canvas.set_draw_color(Color::RGB(60, 60, 70));
+        canvas.fill_rect(Rect::new(20, y, 200, 20))?;
+        canvas.set_draw_color(color);
+        canvas.fill_rect(Rect::new(20, y, (value.clamp(0, 25) * 8) as u32, 20))?;
+        canvas.set_draw_color(Color::WHITE);
+        canvas.fill_rect(Rect::new(20 + mark.clamp(0, 25) as i32 * 8, y - 2, 2, 24))?;
+        draw_number(canvas, value as i32, 232, y + 2, Color::WHITE, FONT_SCALE)?;
+    }
+
+    let seasons = [Season::Spring, Season::Summer, Season::Autumn, Season::Winter];
+    for (index, &season) in seasons.iter().enumerate() {
+        let rect = Rect::new(20 + index as i32 * 28, 210, 22, 22);
+        canvas.set_draw_color(season_color(season));
+        if season == state.season {
+            canvas.fill_rect(rect)?;
+        } else {
+            canvas.draw_rect(rect)?;
+        }
+    }
+    for step in 0..state.rules().doom_timer_limit {
+        let rect = Rect::new(20 + step as i32 * 28, 246, 22, 22);
+        canvas.set_draw_color(Color::RGB(220, 40, 40));
+        if step < state.doom_timer {
+            canvas.fill_rect(rect)?;
+        } else {
+            canvas.draw_rect(rect)?;
+        }
+    }
+    Ok(())
+}
+
+fn draw_number<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    number: i32,
+    x: i32,
+    y: i32,
+    color: Color,
+    scale: u32,
+) -> Result<(), String> {
+    canvas.set_draw_color(color);
+    let text = number.to_string();
+    for (position, character) in text.chars().enumerate() {
+        let glyph = match character {
+            '-' => DIGITS[10],
+            digit => DIGITS[digit.to_digit(10).unwrap() as usize],
+        };
+        let left = x + position as i32 * 4 * scale as i32;
+        for bit in (0..15).filter(|bit| glyph & (1 << (14 - bit)) != 0) {
+            let (column, row) = (bit % 3, bit / 3);
+            canvas.fill_rect(Rect::new(left + column * scale as i32, y + row * scale as i32, scale, scale))?;
+        }
+    }
+    Ok(())
+}
+
+impl Board<'_> {
+    /// The largest radius up to `TILE_RADIUS` that fits the whole layout into the map area,
+    /// centred on `MAP_CENTER`.
+    fn fit(layout: &MapLayout) -> Board<'_> {
+        let positions = layout.positions();
+        let extent = |coordinate: fn(&(i32, i32)) -> i32| {
+            let min = positions.iter().map(coordinate).min().unwrap_or(0) as f64;
+            let max = positions.iter().map(coordinate).max().unwrap_or(0) as f64;
+            (max - min, (max + min) / 2.0)
+        };
+        let ((columns, middle_column), (half_rows, middle_row)) = (extent(|p| p.0), extent(|p| p.1));
+        let half_row_height = 3f64.sqrt() / 2.0;
+        let radius = TILE_RADIUS
+            .min(MAP_AREA.0 / (1.5 * columns + 2.0))
+            .min(MAP_AREA.1 / (half_row_height * (half_rows + 2.0)));
+        Board {
+            positions,
+            radius,
+            center: (
+                MAP_CENTER.0 - middle_column * 1.5 * radius,
+                MAP_CENTER.1 - middle_row * half_row_height * radius,
+            ),
+        }
+    }
+
+    fn center(&self, tile: usize) -> (f64, f64) {
+        let (column, half_row) = self.positions[tile];
+        (
+            self.center.0 + column as f64 * 1.5 * self.radius,
+            self.center.1 + half_row as f64 * 3f64.sqrt() / 2.0 * self.radius,
+        )
+    }
+
+    /// A length on a full size tile, scaled to this board's tiles.
+    fn scaled(&self, length: f64) -> f64 {
+        length * self.radius / TILE_RADIUS
+    }
+
+    fn font_scale(&self) -> u32 {
+        (self.scaled(FONT_SCALE as f64).round() as u32).max(1)
+    }
+}
+
+/// Flat-top hexagon, filled one scanline at a time.
+fn fill_hex<T: RenderTarget>(canvas: &mut Canvas<T>, x: f64, y: f64, radius: f64, color: Color) -> Result<(), String> {
+    canvas.set_draw_color(color);
+    let half_height = (3f64.sqrt() / 2.0 * radius) as i32;
+    let lines: Vec<Rect> = (-half_height..=half_height)
+        .map(|dy| {
+            let half_width = radius - dy.abs() as f64 / 3f64.sqrt();
+            Rect::new((x - half_width) as i32, y as i32 + dy, (2.0 * half_width) as u32, 1)
+        })
+        .collect();
+    canvas.fill_rects(&lines)
+}
+
+fn outline_hex<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    x: f64,
+    y: f64,
+    radius: f64,
+    color: Color,
+) -> Result<(), String> {
+    canvas.set_draw_color(color);
+    let corners: Vec<Point> = (0..=6)
+        .map(|corner| {
+            let angle = std::f64::consts::PI / 3.0 * corner as f64;
+            Point::new((x + radius * angle.cos()) as i32, (y + radius * angle.sin()) as i32)
+        })
+        .collect();
+    canvas.draw_lines(corners.as_slice())
+}
+
+fn window_title(state: &GameState) -> String {
+    format!(
+        "Terra2 - {:?} - Doom timer {} - {:?}",
+        state.season, state.doom_timer, state.status
+    )
+}
+
+pub fn landscape_color(landscape: Landscape) -> Color {
+    match landscape {
+        Landscape::Plains => Color::RGB(170, 200, 90),
+        Landscape::Ocean => Color::RGB(40, 90, 180),
+        Landscape::Mountain => Color::RGB(130, 120, 110),
+        Landscape::Swamp => Color::RGB(80, 110, 80),
+        Landscape::Desert => Color::RGB(220, 190, 120),
+        Landscape::Forest => Color::RGB(30, 110, 50),
+    }
+}
+
+/// Economy buildings are red to orange, sustainability green, education blue.
+fn building_color(building: Building) -> Color {
+    match building {
+        Building::Factory => Color::RGB(180, 40, 40),
+        Building::Store => Color::RGB(220, 110, 50),
+        Building::CoalPowerPlant => Color::RGB(70, 50, 50),
+        Building::Trees => Color::RGB(20, 140, 40),
+        Building::River => Color::RGB(60, 150, 220),
+        Building::Livestock => Color::RGB(160, 120, 70),
+        Building::Field => Color::RGB(230, 210, 80),
+        Building::SolarPark => Color::RGB(250, 230, 20),
+        Building::OffshoreTurbines => Color::RGB(200, 230, 250),
+        Building::Biotope => Color::RGB(100, 200, 120),
+        Building::NationalPark => Color::RGB(40, 180, 90),
+        Building::EnvironmentalProtectionArea => Color::RGB(10, 90, 40),
+        Building::School => Color::RGB(90, 120, 240),
+        Building::Museum => Color::RGB(140, 100, 220),
+        Building::Zoo => Color::RGB(60, 170, 170),
+        Building::Library => Color::RGB(110, 80, 180),
+        Building::University => Color::RGB(40, 60, 170),
+        Building::Empty => Color::RGB(45, 45, 50),
+    }
+}
+
+fn season_color(season: Season) -> Color {
+    match season {
+        Season::Spring => Color::RGB(120, 220, 120),
+        Season::Summer => Color::RGB(240, 220, 60),
+        Season::Autumn => Color::RGB(220, 130, 40),
+        Season::Winter => Color::RGB(230, 230, 250),
+    }
+}
+
+fn dim(color: Color) -> Color {
+    Color::RGB(color.r / 2, color.g / 2, color.b / 2)
+}
+
+/// Minimal PNG encoder: 8 bit RGB, no filters and uncompressed deflate blocks.
+fn write_png(out: &mut impl Write, width: u32, height: u32, rgb: &[u8]) -> std::io::Result<()> {
+    let mut raw = Vec::with_capacity(rgb.len() + height as usize);
+    for row in rgb.chunks(3 * width as usize) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = raw.chunks(0xFFFF).collect();
+    for (index, block) in blocks.iter().enumerate() {
+        zlib.push((index == blocks.len() - 1) as u8);
+        zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+    write_chunk(out, b"IHDR", &header)?;
+    write_chunk(out, b"IDAT", &zlib)?;
+    write_chunk(out, b"IEND", &[])?;
+    out.flush()
+}
+
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&crc32(kind.iter().chain(data)).to_be_bytes())
+}
+
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    !bytes.fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (a, b) = bytes.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headless_render_colours_tiles_by_landscape() {
+        let state = GameState::initialize_with_seed(4);
+        let surface = render_surface(&state, View::default()).unwrap();
+        let pitch = surface.pitch() as usize;
+
+        let board = Board::fit(state.layout());
+        for tile in [0, 6] {
+            let (x, y) = board.center(tile);
+            let (x, y) = (x as usize, y as usize - 20);
+            let pixel = surface.with_lock(|pixels| pixels[y * pitch + 3 * x..y * pitch + 3 * x + 3].to_vec());
+            let color = landscape_color(state.tiles[tile].landscape);
+            let expected = if state.tiles[tile].usable { color } else { dim(color) };
+            assert_eq!(pixel, vec![expected.r, expected.g, expected.b], "tile {tile}");
+        }
+    }
+
+    #[test]
+    fn render_png_writes_a_png() {
+        let path = std::env::temp_dir().join(format!("terra2_render_{}.png", std::process::id()));
+        render_png(&GameState::initialize_with_seed(4), View { selected: Some(6) }, &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(bytes.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(&bytes[12..16], b"IHDR");
+        assert!(bytes.ends_with(&[0xAE, 0x42, 0x60, 0x82]));
+    }
+
+    #[test]
+    fn clicks_pick_tiles_and_legal_actions() {
+        let state = GameState::initialize_with_seed(4);
+        let board = Board::fit(state.layout());
+        let (x, y) = board.center(6);
+        assert_eq!(hit_test(&state, View::default(), x as i32, y as i32), Click::Tile(6));
+
+        let view = View { selected: Some(6) };
+        let (x, y) = board.center(2);
+        assert_eq!(
+            hit_test(&state, view, x as i32, y as i32),
+            Click::Action(BuildInfrastructure(6, 2))
+        );
+        for (rect, action, index) in action_buttons(&state, view) {
+            assert_eq!(state.legal_actions[index], action);
+            assert_eq!(
+                hit_test(&state, view, rect.x() + 1, rect.y() + 1),
+                Click::Action(action)
+            );
+        }
+        assert_eq!(hit_test(&state, view, 5, 5), Click::Nothing);
+
+        let mut state = state;
+        state.resources.education_culture = 10;
+        // Recomputes the legal actions for the new science
+        state.set_rules(state.rules().clone());
+        let buttons = action_buttons(&state, view);
+        assert_eq!(buttons.len(), 16);
+        let &(rect, action, _) = buttons.last().unwrap();
+        assert_eq!(action, Build(Building::University, 6));
+        assert!(rect.right() <= WIDTH as i32);
+        assert_eq!(hit_test(&state, view, rect.right() - 1, rect.bottom() - 1), Click::Action(action));
+    }
+
+    #[test]
+    fn big_layouts_are_scaled_to_fit() {
+        let classic = MapLayout::classic();
+        let classic = Board::fit(&classic);
+        assert_eq!((classic.radius, classic.center), (TILE_RADIUS, MAP_CENTER));
+
+        let layout = MapLayout::hex(5).unwrap();
+        let board = Board::fit(&layout);
+        assert!(board.radius < TILE_RADIUS);
+        for tile in 0..layout.len() {
+            let (x, y) = board.center(tile);
+            assert!(x - board.radius >= MAP_CENTER.0 - MAP_AREA.0 / 2.0 - 1e-6, "tile {tile} at {x}");
+            assert!(x + board.radius <= MAP_CENTER.0 + MAP_AREA.0 / 2.0 + 1e-6, "tile {tile} at {x}");
+            let half_height = 3f64.sqrt() / 2.0 * board.radius;
+            assert!(y + half_height <= MAP_CENTER.1 + MAP_AREA.1 / 2.0 + 1e-6, "tile {tile} at {y}");
+        }
+    }
+}
@@ -1,104 +1,454 @@
 #![feature(trivial_bounds)]
 #![feature(test)]
 
-use crate::game::ai::search_best_move;
+use crate::command::Command;
+use crate::game::ai::{SearchConfig, SearchReport, Searcher};
 use crate::game::game_state::Status::{Loss, Running, Win};
 use crate::game::game_state::{Action, GameState};
-use rustc_hash::FxHashMap;
-use std::collections::HashMap;
+use crate::game::env;
+use crate::game::env::TerraEnv;
+use crate::game::history::Game;
+use crate::game::map_generator::{Difficulty, GeneratedMap, GeneratorConfig};
+use crate::game::map_layout::MapLayout;
+use crate::game::reinforcement_ai::RewardShaping;
+use crate::game::replay::{Annotation, Replay};
+use crate::game::ruleset::Ruleset;
+use crate::game::training;
+use crate::game::training::{Checkpoint, Training, TrainingConfig, DEFAULT_CHECKPOINT_PATH};
 use std::io;
-use std::time::Instant;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
+pub mod ascii_map;
+pub mod command;
 pub mod game;
+pub mod gui;
 
-//static mut transposition_table: Option<FxHashMap<u64, i16>> = None;
+const SEARCH_BUDGET: Duration = Duration::from_secs(2);
+const ANNOTATION_DEPTH: u16 = 3;
 
 fn main() {
-    /*//let file = File::open("saved.json").unwrap();
-    //let map: HashMap<GameState, HashMap<Action, f64>> = serde_json::from_reader(&file).unwrap();
-    let mut trainer = AgentTrainer::new();
-    let mut training_game_state = GameState::initialize();
-    //trainer.import_state(map);
-
-    for i in (0..10000) {
-        let mut agent = MyAgent { state: training_game_state.clone() };
-        trainer.train(&mut agent,
-                      &QLearning::new(0.2, 0.1, 0.0),
-                      &mut SinkStates {},
-                      &RandomExploration::new());
-    }
-
-    //let file = File::create("saved.json").unwrap();
-    //serde_json::to_writer(file, &trainer.export_learned_values()).unwrap();
-
-    let mut game_state = training_game_state.clone();
-    for action in &game_state.legal_actions {
-        let eval = trainer.expected_value(&game_state, action);
-        println!("Action: {action:?}, Eval: {eval:?}")
-    }*/
-    let mut game_state = GameState::initialize();
-    let mut input_string = String::new();
+    let args: Vec<String> = std::env::args().collect();
+    let colour = args.iter().any(|arg| arg == "--color");
+    let rules = match parse_rules(args.iter().cloned()) {
+        Ok(rules) => rules,
+        Err(error) => {
+            println!("{error}");
+            return;
+        }
+    };
+    let layout = match parse_layout(args.iter().cloned()) {
+        Ok(layout) => layout,
+        Err(error) => {
+            println!("{error}");
+            return;
+        }
+    };
+    let generator = match parse_generator_config(&args) {
+        Ok(generator) => generator,
+        Err(error) => {
+            println!("{error}");
+            return;
+        }
+    };
+    if args.get(1).map(String::as_str) == Some("replay") {
+        match args.get(2) {
+            Some(path) => view_replay(path, rules, colour),
+            None => println!("Usage: terra2 replay <file>"),
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("train") {
+        if let Err(error) = run_training(&args[2..], rules, layout) {
+            println!("{error}");
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("env") {
+        if let Err(error) = run_env(&args[2..], rules, layout) {
+            eprintln!("{error}");
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("render") {
+        let Some(path) = args.get(2) else {
+            println!("Usage: terra2 render <file.png> [--seed N] [--layout L]");
+            return;
+        };
+        let seed = match parse_seed(args[3..].iter().cloned()) {
+            Ok(seed) => seed,
+            Err(error) => {
+                println!("{error}");
+                return;
+            }
+        };
+        let state = match game::map_generator::generate(seed, rules, layout, &generator) {
+            Ok(map) => map.state,
+            Err(error) => {
+                println!("{error}");
+                return;
+            }
+        };
+        match gui::render_png(&state, gui::View::default(), path) {
+            Ok(()) => println!("Seed {} rendered to {path}", state.seed),
+            Err(error) => println!("Could not render to {path}: {error}"),
+        }
+        return;
+    }
 
-    game_state.tiles.iter().for_each(|i| {
-        let spaces = i.spaces;
-        println!(
-            "Landscape {:?}, Spaces: {:?}, Connections {:?}",
-            i.landscape, spaces, i.connections
-        )
-    });
-    println!(
-        "Co2: {}, Tech/Econ: {}, Sustainability: {}, Edu/Cult: {}, Co2 per Year: {}",
-        &game_state.resources.instant_co2,
-        &game_state.resources.tech_economy,
-        &game_state.resources.sustainability,
-        &game_state.resources.education_culture,
-        &game_state.resources.yearly_co2,
-    );
-    for i in 0..game_state.legal_actions.len() {
-        println!("{}. {:?}", i, game_state.legal_actions[i])
+    let use_gui = args.iter().any(|arg| arg == "--gui");
+    let seed = match parse_seed(args.into_iter()) {
+        Ok(seed) => seed,
+        Err(error) => {
+            println!("{error}");
+            return;
+        }
+    };
+    let GeneratedMap { state, report, .. } = match game::map_generator::generate(seed, rules, layout, &generator) {
+        Ok(map) => map,
+        Err(error) => {
+            println!("{error}");
+            return;
+        }
+    };
+    println!("Seed: {}, fairness {} ({:?})", state.seed, report.fairness, report.difficulty());
+    let mut game = Game::new(state);
+    if use_gui {
+        if let Err(error) = gui::run(game) {
+            println!("Could not start the GUI: {error}");
+        }
+        return;
     }
+    let mut input_string = String::new();
+
+    print_tiles(game.state(), colour);
+    print_resources(game.state());
+    print_legal_actions(game.state());
+    println!("Type help for a list of commands");
 
-    unsafe {
-        while input_string.trim() != "x" && game_state.status == Running {
-            let now = Instant::now();
-            //transposition_table = Some(FxHashMap::default());
-            let (eval, best_move) = search_best_move(5, &game_state);
-            println!("Best move: {best_move:?}, Eval: {eval:?}");
-            let elapsed = now.elapsed();
-            println!("Elapsed: {:.2?}", elapsed);
-            let action = parse_input(&game_state, &mut input_string);
-            game_state.advance(action);
-            print_tiles(&game_state);
-            print_resources(&game_state);
-            print_legal_actions(&game_state);
+    let mut searcher = Searcher::with_config(SearchConfig {
+        threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        ..SearchConfig::default()
+    });
+    let mut report = None;
+    while game.state().status == Running {
+        let current = report.get_or_insert_with(|| {
+            let current = searcher.search_best_move_timed(game.state(), SEARCH_BUDGET);
+            println!("Best move: {:?}, Eval: {:?}", current.best_move, current.eval);
+            print_plan(&current.principal_variation);
+            current
+        });
+        let command = match read_command(game.state(), &mut input_string) {
+            Some(command) => command,
+            None => break,
+        };
+        match command {
+            Command::Play(action) => {
+                if let Err(error) = game.play(action) {
+                    println!("Can't do that: {error}");
+                    continue;
+                }
+            }
+            Command::Hint => {
+                print_hint(current);
+                continue;
+            }
+            Command::Undo => {
+                if !game.undo() {
+                    println!("Nothing to undo");
+                    continue;
+                }
+            }
+            Command::Redo => {
+                if !game.redo() {
+                    println!("Nothing to redo");
+                    continue;
+                }
+            }
+            Command::Save(path) => {
+                match game.save(&path) {
+                    Ok(()) => println!("Saved to {path}"),
+                    Err(error) => println!("Could not save to {path}: {error}"),
+                }
+                continue;
+            }
+            Command::ExportReplay(path) => {
+                match export_replay(&game, &path) {
+                    Ok(()) => println!("Replay written to {path}"),
+                    Err(error) => println!("Could not write replay to {path}: {error}"),
+                }
+                continue;
+            }
+            Command::Load(path) => match Game::load_with_rules(&path, game.state().rules().clone()) {
+                Ok(loaded) => {
+                    game = loaded;
+                    println!("Loaded {path}, seed {}, turn {}", game.state().seed, game.turn());
+                }
+                Err(error) => {
+                    println!("Could not load {path}: {error}");
+                    continue;
+                }
+            },
+            Command::Help => {
+                println!("{}", command::HELP);
+                continue;
+            }
+            Command::Quit => break,
         }
+        report = None;
+        print_tiles(game.state(), colour);
+        print_resources(game.state());
+        print_legal_actions(game.state());
     }
 
-    if game_state.status == Win {
+    if game.state().status == Win {
         println!("You won!")
     }
-    if game_state.status == Loss {
+    if game.state().status == Loss {
         println!("You lose!")
     }
     println!("See you later!");
 }
 
-fn parse_input(game_state: &GameState, mut input_string: &mut String) -> Action {
-    input_string.clear();
-    io::stdin().read_line(&mut input_string).unwrap();
-    let input: &str = input_string.trim();
-    let action_index = input.parse::<usize>().unwrap();
-    game_state.legal_actions[action_index]
-}
+/// Trains a Q-table over whole games and writes a checkpoint after every epoch.
+fn run_training(args: &[String], rules: Arc<Ruleset>, layout: Arc<MapLayout>) -> Result<(), String> {
+    let defaults = TrainingConfig::default();
+    let config = TrainingConfig {
+        episodes: parse_option(args, "--episodes", defaults.episodes)?,
+        epoch_size: parse_option(args, "--epoch-size", defaults.epoch_size)?,
+        alpha: parse_option(args, "--alpha", defaults.alpha)?,
+        gamma: parse_option(args, "--gamma", defaults.gamma)?,
+        epsilon: parse_option(args, "--epsilon", defaults.epsilon)?,
+        initial_value: defaults.initial_value,
+        max_steps: parse_option(args, "--max-steps", defaults.max_steps)?,
+        shaping: parse_shaping(args)?,
+        seed: parse_option(args, "--seed", defaults.seed)?,
+    };
+    let checkpoint_path = parse_option(args, "--checkpoint", DEFAULT_CHECKPOINT_PATH.to_string())?;
+    let mut training = Training::new(config, rules.clone(), layout);
+    if let Some(path) = option_value(args.iter().cloned(), "--resume") {
+        let path = path.ok_or("--resume expects a checkpoint file")?;
+        let checkpoint =
+            Checkpoint::load(&path, &rules).map_err(|error| format!("Could not resume from {path}: {error}"))?;
+        println!("Resuming after {} episodes", checkpoint.episodes);
+        training.resume(checkpoint);
+    }
 
-fn print_tiles(game_state: &GameState) {
-    game_state.tiles.iter().for_each(|i| {
-        let spaces = i.spaces;
+    let mut result = Ok(());
+    training::train(&mut training, |training, report| {
         println!(
-            "Landscape {:?}, Spaces: {:?}, Connections {:?}",
-            i.landscape, spaces, i.connections
-        )
+            "Epoch {}: {} episodes, win rate {:.1}%, {} states",
+            report.epoch,
+            report.episodes,
+            100.0 * report.win_rate(),
+            report.states
+        );
+        if let Err(error) = training.checkpoint().save(&checkpoint_path) {
+            result = Err(format!("Could not write the checkpoint to {checkpoint_path}: {error}"));
+        }
     });
+    result?;
+    println!("Trained {} episodes, checkpoint in {checkpoint_path}", training.episodes());
+    Ok(())
+}
+
+fn parse_shaping(args: &[String]) -> Result<RewardShaping, String> {
+    let defaults = RewardShaping::default();
+    Ok(RewardShaping {
+        co2_penalty: parse_option(args, "--co2-penalty", defaults.co2_penalty)?,
+        win_bonus: parse_option(args, "--win-bonus", defaults.win_bonus)?,
+        par_turns: parse_option(args, "--par-turns", defaults.par_turns)?,
+        loss_penalty: parse_option(args, "--loss-penalty", defaults.loss_penalty)?,
+    })
+}
+
+/// Serves a `TerraEnv` as JSON lines on stdin and stdout, see `game::env::serve`.
+fn run_env(args: &[String], rules: Arc<Ruleset>, layout: Arc<MapLayout>) -> Result<(), String> {
+    let mut env = TerraEnv::new(rules, layout, parse_shaping(args)?);
+    env::serve(&mut env, io::stdin().lock(), io::stdout().lock()).map_err(|error| error.to_string())
+}
+
+/// Reads `<name> <value>`, `default` if the option is not given.
+fn parse_option<T: FromStr>(args: &[String], name: &str, default: T) -> Result<T, String> {
+    match option_value(args.iter().cloned(), name) {
+        None => Ok(default),
+        Some(Some(value)) => value.parse().map_err(|_| format!("{name} can't be {value}")),
+        Some(None) => Err(format!("{name} expects a value")),
+    }
+}
+
+/// The seed given with `--seed`, or a random one without the flag.
+fn parse_seed(args: impl Iterator<Item = String>) -> Result<u64, String> {
+    match option_value(args, "--seed") {
+        None => Ok(rand::random()),
+        Some(seed) => seed
+            .and_then(|seed| seed.parse().ok())
+            .ok_or_else(|| "--seed expects a number".to_string()),
+    }
+}
+
+/// Loads the ruleset given with `--rules <file>`, JSON or TOML, or returns the default one.
+fn parse_rules(args: impl Iterator<Item = String>) -> Result<Arc<Ruleset>, String> {
+    match option_value(args, "--rules") {
+        None => Ok(Ruleset::shared_default()),
+        Some(None) => Err("--rules expects a file".to_string()),
+        Some(Some(path)) => Ruleset::load(&path)
+            .map(Arc::new)
+            .map_err(|error| format!("Could not load rules from {path}: {error}")),
+    }
+}
+
+/// Reads `--layout classic`, `--layout hex:<radius>`, `--layout rect:<width>x<height>` or `--layout <file>`.
+fn parse_layout(args: impl Iterator<Item = String>) -> Result<Arc<MapLayout>, String> {
+    match option_value(args, "--layout") {
+        None => Ok(MapLayout::shared_classic()),
+        Some(None) => Err("--layout expects classic, hex:<radius>, rect:<width>x<height> or a file".to_string()),
+        Some(Some(spec)) => MapLayout::parse(&spec)
+            .map(Arc::new)
+            .map_err(|error| format!("Could not use the layout {spec}: {error}")),
+    }
+}
+
+/// Reads the map generator options `--landscapes Desert=4,Ocean=4,...`, `--resources-within <steps>`,
+/// `--no-adjacent-swamps` and `--difficulty <easy|normal|hard>`.
+fn parse_generator_config(args: &[String]) -> Result<GeneratorConfig, String> {
+    let value = |name: &str| match option_value(args.iter().cloned(), name) {
+        Some(None) => Err(format!("{name} expects a value")),
+        Some(Some(value)) => Ok(Some(value)),
+        None => Ok(None),
+    };
+    let landscape_counts = match value("--landscapes")? {
+        Some(counts) => Some(
+            counts
+                .split(',')
+                .map(|count| {
+                    let (landscape, number) = count.split_once('=')?;
+                    Some((landscape.trim().parse().ok()?, number.trim().parse().ok()?))
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| format!("--landscapes expects <landscape>=<count>,..., not {counts}"))?,
+        ),
+        None => None,
+    };
+    let resources_within = match value("--resources-within")? {
+        Some(steps) => Some(steps.parse().map_err(|_| format!("{steps} is not a number of steps"))?),
+        None => None,
+    };
+    let difficulty = match value("--difficulty")? {
+        Some(difficulty) => Some(
+            difficulty
+                .parse::<Difficulty>()
+                .map_err(|_| format!("--difficulty expects easy, normal or hard, not {difficulty}"))?,
+        ),
+        None => None,
+    };
+    Ok(GeneratorConfig {
+        landscape_counts,
+        resources_within,
+        no_adjacent_swamps: args.iter().any(|arg| arg == "--no-adjacent-swamps"),
+        difficulty,
+        ..GeneratorConfig::default()
+    })
+}
+
+/// `None` if the option is not given, `Some(None)` if it is given without a value.
+fn option_value(mut args: impl Iterator<Item = String>, name: &str) -> Option<Option<String>> {
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix(name) {
+            Some("") => return Some(args.next()),
+            Some(value) => {
+                if let Some(value) = value.strip_prefix('=') {
+                    return Some(Some(value.to_string()));
+                }
+            }
+            None => {}
+        }
+    }
+    None
+}
+
+/// Prompts until the player enters a valid command, `None` once stdin is closed.
+fn read_command(game_state: &GameState, input_string: &mut String) -> Option<Command> {
+    loop {
+        print!("> ");
+        io::stdout().flush().ok()?;
+        input_string.clear();
+        if io::stdin().read_line(input_string).ok()? == 0 {
+            return None;
+        }
+        match command::parse(input_string, game_state) {
+            Ok(command) => return Some(command),
+            Err(error) => println!("{error}"),
+        }
+    }
+}
+
+fn print_hint(report: &SearchReport) {
+    println!("Best move: {:?}, Eval: {:?}", report.best_move, report.eval);
+    print_plan(&report.principal_variation);
+    if let Some(deepest) = report.depths.last() {
+        println!(
+            "Depth: {}, Nodes: {}, Elapsed: {:.2?}",
+            deepest.depth, deepest.nodes, deepest.elapsed
+        );
+    }
+}
+
+/// Writes the game as a replay, every move annotated with a shallow search.
+fn export_replay(game: &Game, path: &str) -> io::Result<()> {
+    let mut replay = Replay::from_game(game);
+    let mut searcher = Searcher::default();
+    replay
+        .annotate(|state| {
+            let (eval, best_move, _) = searcher.search_best_move(ANNOTATION_DEPTH, state);
+            Annotation { eval, best_move }
+        })
+        .map_err(|(index, error)| io::Error::new(io::ErrorKind::InvalidData, format!("move {index}: {error}")))?;
+    replay.save(path)
+}
+
+/// Steps through a replay, Enter shows the next move.
+fn view_replay(path: &str, rules: Arc<Ruleset>, colour: bool) {
+    let replay = match Replay::load_with_rules(path, rules) {
+        Ok(replay) => replay,
+        Err(error) => {
+            println!("Could not load {path}: {error}");
+            return;
+        }
+    };
+    let states = match replay.states() {
+        Ok(states) => states,
+        Err((index, error)) => {
+            println!("Move {index} can't be played: {error}");
+            return;
+        }
+    };
+
+    println!("Seed: {}, Moves: {}", replay.seed, replay.moves.len());
+    print_tiles(&states[0], colour);
+    print_resources(&states[0]);
+    let mut input_string = String::new();
+    for (index, (replay_move, state)) in replay.moves.iter().zip(&states[1..]).enumerate() {
+        input_string.clear();
+        if io::stdin().read_line(&mut input_string).is_ok_and(|_| input_string.trim() == "x") {
+            break;
+        }
+        println!("Move {}: {:?}", index + 1, replay_move.action);
+        if let Some(annotation) = replay_move.annotation {
+            println!("AI eval: {}, AI preferred: {:?}", annotation.eval, annotation.best_move);
+        }
+        print_tiles(state, colour);
+        print_resources(state);
+    }
+    if let Some(state) = states.last() {
+        println!("Result: {:?}", state.status);
+    }
+}
+
+fn print_tiles(game_state: &GameState, colour: bool) {
+    print!("{}", ascii_map::render(game_state, colour));
 }
 
 fn print_resources(game_state: &GameState) {
@@ -112,6 +462,13 @@ fn print_resources(game_state: &GameState) {
     );
 }
 
+fn print_plan(principal_variation: &[Action]) {
+    println!("Plan:");
+    for (turn, action) in principal_variation.iter().enumerate() {
+        println!("  {}. {:?}", turn + 1, action)
+    }
+}
+
 fn print_legal_actions(game_state: &GameState) {
     for i in 0..game_state.legal_actions.len() {
         println!("{}. {:?}", i, game_state.legal_actions[i])